@@ -1,5 +1,8 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{BufRead, Lines};
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Itertools;
+use anyhow::Result;
 
 #[aoc_generator(day1)]
 fn parse(input: &str) -> Vec<Vec<u32>> {
@@ -9,26 +12,150 @@ fn parse(input: &str) -> Vec<Vec<u32>> {
         .collect()
 }
 
-#[aoc(day1, part1)]
-fn part1(input: &[Vec<u32>]) -> u32 {
+pub fn elf_totals(input: &[Vec<u32>]) -> Vec<(usize, u64)> {
     input.iter()
-        .map(|calorie_counts| calorie_counts.iter().sum())
-        .max()
+        .map(|calorie_counts| calorie_counts.iter().map(|&calories| calories as u64).sum())
+        .enumerate()
+        .collect()
+}
+
+pub struct ElfTotals<R> {
+    lines: Lines<R>,
+    elf: usize,
+    line_number: usize,
+}
+
+impl<R: BufRead> ElfTotals<R> {
+    fn fail(&mut self, error: anyhow::Error) -> Option<Result<(usize, u64)>> {
+        for line in self.lines.by_ref() {
+            self.line_number += 1;
+
+            if line.map_or(true, |line| line.is_empty()) {
+                break;
+            }
+        }
+
+        self.elf += 1;
+        Some(Err(error))
+    }
+}
+
+impl<R: BufRead> Iterator for ElfTotals<R> {
+    type Item = Result<(usize, u64)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut total: Option<u64> = None;
+
+        while let Some(line) = self.lines.next() {
+            self.line_number += 1;
+
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => return self.fail(error.into()),
+            };
+
+            if line.is_empty() {
+                if total.is_some() {
+                    break;
+                }
+
+                continue;
+            }
+
+            let calories = match line.parse::<u64>() {
+                Ok(calories) => calories,
+                Err(error) => {
+                    let error = anyhow::Error::new(error).context(format!("Invalid calorie count on line {}", self.line_number));
+                    return self.fail(error);
+                },
+            };
+
+            match total.unwrap_or_default().checked_add(calories) {
+                Some(sum) => total = Some(sum),
+                None => return self.fail(anyhow::anyhow!("Calorie total overflows on line {}", self.line_number)),
+            }
+        }
+
+        let elf = self.elf;
+        self.elf += 1;
+
+        total.map(|total| Ok((elf, total)))
+    }
+}
+
+pub fn read_elf_totals<R: BufRead>(reader: R) -> ElfTotals<R> {
+    ElfTotals {
+        lines: reader.lines(),
+        elf: 0,
+        line_number: 0,
+    }
+}
+
+pub fn top_k(totals: impl IntoIterator<Item=(usize, u64)>, k: usize) -> Vec<(usize, u64)> {
+    let mut heap = BinaryHeap::new();
+
+    for (elf, total) in totals {
+        heap.push(Reverse((total, Reverse(elf))));
+
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((total, Reverse(elf)))| (elf, total))
+        .collect()
+}
+
+pub fn percentile(totals: &[(usize, u64)], p: f64) -> Option<u64> {
+    if totals.is_empty() || !(0.0..=100.0).contains(&p) {
+        return None;
+    }
+
+    let rank = ((p / 100.0 * totals.len() as f64).ceil() as usize).max(1);
+    let mut values = totals.iter().map(|&(_, total)| total).collect::<Vec<_>>();
+
+    Some(*values.select_nth_unstable(rank - 1).1)
+}
+
+pub fn median(totals: &[(usize, u64)]) -> Option<f64> {
+    let mut values = totals.iter().map(|&(_, total)| total).collect::<Vec<_>>();
+    let middle = values.len() / 2;
+
+    if values.is_empty() {
+        return None;
+    }
+
+    let upper = *values.select_nth_unstable(middle).1;
+
+    if values.len() % 2 == 1 {
+        return Some(upper as f64);
+    }
+
+    let lower = values[..middle].iter().copied().max().unwrap_or(upper);
+    Some((lower as f64 + upper as f64) / 2.0)
+}
+
+#[aoc(day1, part1)]
+fn part1(input: &[Vec<u32>]) -> u64 {
+    top_k(elf_totals(input), 1)
+        .first()
+        .map(|&(_, total)| total)
         .unwrap_or_default()
 }
 
 #[aoc(day1, part2)]
-fn part2(input: &[Vec<u32>]) -> u32 {
-    input.iter()
-        .map(|calorie_counts| calorie_counts.iter().sum::<u32>())
-        .sorted()
-        .rev()
-        .take(3)
+fn part2(input: &[Vec<u32>]) -> u64 {
+    top_k(elf_totals(input), 3)
+        .iter()
+        .map(|&(_, total)| total)
         .sum()
 }
 
 #[cfg(test)]
 mod tests {
+    use itertools::process_results;
     use super::*;
 
     #[test]
@@ -40,4 +167,46 @@ mod tests {
     fn part2_example1() {
         assert_eq!(45000, part2(&parse(include_str!("../input/2022/day1.part2.test.45000.txt"))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn top_k_example1() {
+        let totals = elf_totals(&parse(include_str!("../input/2022/day1.part1.test.24000.txt")));
+        assert_eq!(vec![(3, 24000), (2, 11000)], top_k(totals.clone(), 2));
+        assert_eq!(5, top_k(totals, usize::MAX).len());
+    }
+
+    #[test]
+    fn statistics_example1() {
+        let totals = elf_totals(&parse(include_str!("../input/2022/day1.part1.test.24000.txt")));
+        assert_eq!(Some(10000.0), median(&totals));
+        assert_eq!(Some(4000), percentile(&totals, 0.0));
+        assert_eq!(Some(6000), percentile(&totals, 40.0));
+        assert_eq!(Some(24000), percentile(&totals, 100.0));
+        assert_eq!(Some(10500.0), median(&totals[1..]));
+    }
+
+    #[test]
+    fn read_elf_totals_example1() {
+        let reader = include_str!("../input/2022/day1.part1.test.24000.txt").as_bytes();
+        let top = process_results(read_elf_totals(reader), |totals| top_k(totals, 3)).unwrap();
+        assert_eq!(vec![(3, 24000), (2, 11000), (4, 10000)], top);
+    }
+
+    #[test]
+    fn read_elf_totals_invalid() {
+        assert!(read_elf_totals("1000\nabc\n".as_bytes()).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn read_elf_totals_skips_invalid_elf() {
+        let totals = read_elf_totals("1\nx\n2\n\n3\n\n4\n18446744073709551615\n5\n\n6".as_bytes()).collect::<Vec<_>>();
+        let errors = totals.iter().map(|total| total.as_ref().map_err(|error| error.to_string())).collect::<Vec<_>>();
+
+        assert_eq!(vec![
+            Err("Invalid calorie count on line 2".to_string()),
+            Ok(&(1, 3)),
+            Err("Calorie total overflows on line 8".to_string()),
+            Ok(&(3, 6)),
+        ], errors);
+    }
+}
@@ -1,116 +1,467 @@
+use std::cmp::Ordering;
+use std::fmt;
 use std::str::FromStr;
 use aoc_runner_derive::{aoc, aoc_generator};
-use Play::{Paper, Rock, Scissors};
+use itertools::Itertools;
 
-#[derive(Debug, Copy, Clone)]
-enum AocError {
-    InvalidPlay,
-    InvalidStrategy,
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AocError {
+    InvalidPlay(String),
+    InvalidStrategy(String),
+    InvalidGame(String),
+    MalformedLine(String),
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum Play {
-    Rock,
-    Paper,
-    Scissors,
+impl fmt::Display for AocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AocError::InvalidPlay(code) => write!(f, "Invalid play: {}", code),
+            AocError::InvalidStrategy(code) => write!(f, "Invalid strategy: {}", code),
+            AocError::InvalidGame(reason) => write!(f, "Invalid game: {}", reason),
+            AocError::MalformedLine(line) => write!(f, "Malformed input line: {}", line),
+        }
+    }
 }
 
-impl FromStr for Play {
-    type Err = AocError;
+impl std::error::Error for AocError {}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "A" => Ok(Rock),
-            "B" => Ok(Paper),
-            "C" => Ok(Scissors),
-            _ => Err(AocError::InvalidPlay),
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Move {
+    pub name: String,
+    pub value: u32,
+    pub opponent_code: String,
+    pub response_code: String,
+}
+
+impl Move {
+    pub fn new(name: &str, value: u32, opponent_code: &str, response_code: &str) -> Move {
+        Move {
+            name: name.to_string(),
+            value,
+            opponent_code: opponent_code.to_string(),
+            response_code: response_code.to_string(),
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Strategy {
-    X,
-    Y,
-    Z,
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Game {
+    moves: Vec<Move>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Play(pub usize);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Strategy(pub usize);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Loss,
+    Draw,
+    Win,
 }
 
-impl FromStr for Strategy {
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Decoding {
+    AsMove,
+    AsOutcome,
+}
+
+impl Game {
+    pub fn new(moves: Vec<Move>) -> Result<Game, AocError> {
+        if moves.len().is_multiple_of(2) {
+            return Err(AocError::InvalidGame(format!("a cyclic game needs an odd number of moves, got {}", moves.len())));
+        }
+
+        if let Some(code) = moves.iter().map(|m| &m.opponent_code).duplicates().next() {
+            return Err(AocError::InvalidGame(format!("duplicate opponent code {}", code)));
+        }
+
+        if let Some(code) = moves.iter().map(|m| &m.response_code).duplicates().next() {
+            return Err(AocError::InvalidGame(format!("duplicate response code {}", code)));
+        }
+
+        Ok(Game { moves })
+    }
+
+    pub fn rock_paper_scissors() -> Game {
+        Game {
+            moves: vec![
+                Move::new("Rock", 1, "A", "X"),
+                Move::new("Paper", 2, "B", "Y"),
+                Move::new("Scissors", 3, "C", "Z"),
+            ],
+        }
+    }
+
+    pub fn rock_paper_scissors_lizard_spock() -> Game {
+        Game {
+            moves: vec![
+                Move::new("Rock", 1, "A", "V"),
+                Move::new("Spock", 2, "B", "W"),
+                Move::new("Paper", 3, "C", "X"),
+                Move::new("Lizard", 4, "D", "Y"),
+                Move::new("Scissors", 5, "E", "Z"),
+            ],
+        }
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn plays(&self) -> impl Iterator<Item=Play> + Clone {
+        (0..self.moves.len()).map(Play)
+    }
+
+    pub fn beats(&self, a: Play, b: Play) -> bool {
+        let n = self.moves.len();
+        (1..=(n / 2)).contains(&((a.0 + n - b.0) % n))
+    }
+
+    pub fn outcome(&self, other_play: Play, your_play: Play) -> Outcome {
+        if other_play == your_play {
+            Outcome::Draw
+        } else if self.beats(your_play, other_play) {
+            Outcome::Win
+        } else {
+            Outcome::Loss
+        }
+    }
+
+    pub fn parse_play(&self, code: &str) -> Result<Play, AocError> {
+        self.moves.iter()
+            .position(|m| m.opponent_code == code)
+            .map(Play)
+            .ok_or_else(|| AocError::InvalidPlay(code.to_string()))
+    }
+
+    pub fn parse_strategy(&self, code: &str) -> Result<Strategy, AocError> {
+        self.moves.iter()
+            .position(|m| m.response_code == code)
+            .map(Strategy)
+            .ok_or_else(|| AocError::InvalidStrategy(code.to_string()))
+    }
+
+    pub fn decode(&self, other_play: Play, strategy: Strategy, decoding: Decoding) -> Play {
+        let n = self.moves.len();
+
+        match decoding {
+            Decoding::AsMove => Play(strategy.0),
+            Decoding::AsOutcome => Play((other_play.0 + strategy.0 + n - n / 2) % n),
+        }
+    }
+}
+
+impl FromStr for Game {
     type Err = AocError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "X" => Ok(Strategy::X),
-            "Y" => Ok(Strategy::Y),
-            "Z" => Ok(Strategy::Z),
-            _ => Err(AocError::InvalidStrategy),
-        }
+        let moves = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                match line.split_ascii_whitespace().collect::<Vec<_>>()[..] {
+                    [name, value, opponent_code, response_code] => Ok(Move::new(
+                        name,
+                        value.parse().map_err(|_| AocError::InvalidGame(format!("invalid value for {}: {}", name, value)))?,
+                        opponent_code,
+                        response_code,
+                    )),
+                    _ => Err(AocError::MalformedLine(line.to_string())),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Game::new(moves)
     }
 }
 
-#[aoc_generator(day2)]
-fn parse(input: &str) -> Vec<(Play, Strategy)> {
+pub fn parse_guide(game: &Game, input: &str) -> Result<Vec<(Play, Strategy)>, AocError> {
     input
         .lines()
-        .map(|line| (
-            line[0..1].parse::<Play>().unwrap(),
-            line[2..3].parse::<Strategy>().unwrap()
-        ))
+        .map(|line| {
+            let (play, strategy) = line
+                .split_once(' ')
+                .ok_or_else(|| AocError::MalformedLine(line.to_string()))?;
+
+            Ok((game.parse_play(play)?, game.parse_strategy(strategy)?))
+        })
         .collect()
 }
 
-fn value(play: Play) -> u32 {
-    match play {
-        Rock => 1,
-        Paper => 2,
-        Scissors => 3,
+#[aoc_generator(day2)]
+fn parse(input: &str) -> Result<Vec<(Play, Strategy)>, AocError> {
+    parse_guide(&Game::rock_paper_scissors(), input)
+}
+
+pub fn score(game: &Game, other_play: Play, your_play: Play) -> u32 {
+    let outcome = match game.outcome(other_play, your_play) {
+        Outcome::Win => 6,
+        Outcome::Draw => 3,
+        Outcome::Loss => 0,
+    };
+
+    game.moves[your_play.0].value + outcome
+}
+
+pub fn total_score(game: &Game, guide: &[(Play, Strategy)], decoding: Decoding) -> u32 {
+    guide.iter()
+        .map(|&(a, b)| (a, game.decode(a, b, decoding)))
+        .map(|(a, b)| score(game, a, b))
+        .sum()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Constraints {
+    pub max_uses: Vec<Option<usize>>,
+    pub draws: Option<usize>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Optimum {
+    pub score: u32,
+    pub responses: Vec<Play>,
+}
+
+struct Edge {
+    from: usize,
+    to: usize,
+    capacity: i64,
+    cost: i64,
+}
+
+fn max_weight_flow(supply: &[usize], capacity: &[usize], limits: &[Vec<usize>], weights: &[Vec<i64>]) -> Option<(i64, Vec<Vec<usize>>)> {
+    let n = supply.len();
+    let (source, sink) = (0, 2 * n + 1);
+    let mut edges: Vec<Edge> = vec![];
+    let add_edge = |edges: &mut Vec<Edge>, from: usize, to: usize, capacity: i64, cost: i64| {
+        edges.push(Edge { from, to, capacity, cost });
+        edges.push(Edge { from: to, to: from, capacity: 0, cost: -cost });
+    };
+
+    for o in 0..n {
+        add_edge(&mut edges, source, 1 + o, supply[o] as i64, 0);
+        add_edge(&mut edges, 1 + n + o, sink, capacity[o] as i64, 0);
+    }
+
+    let pair_edges = edges.len();
+    for o in 0..n {
+        for r in 0..n {
+            add_edge(&mut edges, 1 + o, 1 + n + r, limits[o][r] as i64, -weights[o][r]);
+        }
+    }
+
+    let (mut flow, mut total) = (0, 0);
+
+    loop {
+        let mut distance = vec![i64::MAX; 2 * n + 2];
+        let mut previous = vec![usize::MAX; 2 * n + 2];
+        distance[source] = 0;
+
+        for _ in 0..(2 * n + 1) {
+            for (e, edge) in edges.iter().enumerate() {
+                if edge.capacity > 0 && distance[edge.from] != i64::MAX && distance[edge.from] + edge.cost < distance[edge.to] {
+                    distance[edge.to] = distance[edge.from] + edge.cost;
+                    previous[edge.to] = e;
+                }
+            }
+        }
+
+        if distance[sink] == i64::MAX {
+            break;
+        }
+
+        let path = std::iter::successors(Some(previous[sink]), |&e| Some(previous[edges[e].from]).filter(|&e| e != usize::MAX))
+            .collect::<Vec<_>>();
+        let amount = path.iter().map(|&e| edges[e].capacity).min()?;
+
+        for &e in &path {
+            edges[e].capacity -= amount;
+            edges[e ^ 1].capacity += amount;
+        }
+
+        flow += amount;
+        total -= amount * distance[sink];
+    }
+
+    if flow != supply.iter().sum::<usize>() as i64 {
+        return None;
+    }
+
+    let counts = (0..n)
+        .map(|o| (0..n).map(|r| edges[pair_edges + 2 * (o * n + r) + 1].capacity as usize).collect())
+        .collect();
+
+    Some((total, counts))
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+struct Relaxation<'a> {
+    scores: &'a [Vec<i64>],
+    supply: &'a [usize],
+    capacity: &'a [usize],
+    draws: usize,
+    scale: i64,
+    bound: i64,
+    best: Option<(i64, Vec<Vec<usize>>)>,
+}
+
+impl Relaxation<'_> {
+    // Solves the flow with every draw worth `penalty` more (in units of `scale`), with at least `least[o]`
+    // and at most `most[o]` draws against opponent move `o`.
+    fn solve(&self, least: &[usize], most: &[usize], penalty: i64) -> Option<Vec<Vec<usize>>> {
+        let n = self.supply.len();
+        let supply = (0..n).map(|o| self.supply[o].checked_sub(least[o])).collect::<Option<Vec<_>>>()?;
+        let capacity = (0..n).map(|r| self.capacity[r].checked_sub(least[r])).collect::<Option<Vec<_>>>()?;
+        let limits = (0..n)
+            .map(|o| (0..n).map(|r| if o == r { most[o] - least[o] } else { supply[o] }).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let weights = (0..n)
+            .map(|o| (0..n).map(|r| self.scale * self.scores[o][r] + if o == r { penalty } else { 0 }).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let (_, mut counts) = max_weight_flow(&supply, &capacity, &limits, &weights)?;
+        for (o, &least) in least.iter().enumerate() {
+            counts[o][o] += least;
+        }
+        Some(counts)
+    }
+
+    fn draws(counts: &[Vec<usize>]) -> usize {
+        (0..counts.len()).map(|o| counts[o][o]).sum()
+    }
+
+    fn total(&self, counts: &[Vec<usize>]) -> i64 {
+        counts.iter().zip(self.scores).flat_map(|(row, scores)| row.iter().zip(scores)).map(|(&count, &score)| count as i64 * score).sum()
+    }
+
+    fn accept(&mut self, counts: Vec<Vec<usize>>) {
+        let total = self.total(&counts);
+        if self.best.as_ref().is_none_or(|(best, _)| total > *best) {
+            self.best = Some((total, counts));
+        }
+    }
+
+    // Branch and bound over the number of draws against each opponent move. Penalties are always odd and the
+    // breakpoints of the Lagrangian dual always even, so each relaxation has a unique number of draws; the
+    // dual bound at the breakpoint prunes branches, and a relaxation with exactly the right number of draws
+    // is optimal for its branch.
+    fn branch(&mut self, least: Vec<usize>, most: Vec<usize>) {
+        let Some(fewest) = self.solve(&least, &most, -self.bound) else {
+            return;
+        };
+        let Some(mut most_draws) = self.solve(&least, &most, self.bound) else {
+            return;
+        };
+
+        match (Self::draws(&fewest).cmp(&self.draws), Self::draws(&most_draws).cmp(&self.draws)) {
+            (Ordering::Greater, _) | (_, Ordering::Less) => return,
+            (Ordering::Equal, _) => return self.accept(fewest),
+            (_, Ordering::Equal) => return self.accept(most_draws),
+            _ => {},
+        }
+
+        let (mut below, mut above) = (-self.bound, self.bound);
+        let mut fewer_draws = fewest;
+
+        while above - below > 2 {
+            let penalty = ((below + above) / 2) | 1;
+            let Some(counts) = self.solve(&least, &most, penalty) else {
+                return;
+            };
+
+            match Self::draws(&counts).cmp(&self.draws) {
+                Ordering::Equal => return self.accept(counts),
+                Ordering::Less => (below, fewer_draws) = (penalty, counts),
+                Ordering::Greater => (above, most_draws) = (penalty, counts),
+            }
+        }
+
+        let breakpoint = below + 1;
+        let dual = self.scale * self.total(&fewer_draws) + breakpoint * (Self::draws(&fewer_draws) as i64 - self.draws as i64);
+        if self.best.as_ref().is_some_and(|(best, _)| *best >= dual.div_euclid(self.scale)) {
+            return;
+        }
+
+        let Some(o) = (0..least.len()).find(|&o| fewer_draws[o][o] != most_draws[o][o]) else {
+            return;
+        };
+        let split = (fewer_draws[o][o] + most_draws[o][o]) / 2;
+
+        let mut lower = most.clone();
+        lower[o] = split;
+        self.branch(least.clone(), lower);
+
+        let mut upper = least;
+        upper[o] = split + 1;
+        self.branch(upper, most);
     }
 }
 
-fn score(other_play: Play, your_play: Play) -> u32 {
-    let outcome = match (other_play, your_play) {
-        (Rock, Paper) => 6,
-        (Paper, Scissors) => 6,
-        (Scissors, Rock) => 6,
-        (a, b) if a == b => 3,
-        (_, _) => 0,
+pub fn optimise(game: &Game, opponent_plays: &[Play], constraints: &Constraints) -> Option<Optimum> {
+    let n = game.moves.len();
+    let counts = opponent_plays.iter().counts();
+    let supply = game.plays().map(|play| *counts.get(&play).unwrap_or(&0)).collect::<Vec<_>>();
+    let capacity = (0..n)
+        .map(|r| constraints.max_uses.get(r).copied().flatten().unwrap_or(opponent_plays.len()).min(opponent_plays.len()))
+        .collect::<Vec<_>>();
+    let scores = game.plays()
+        .map(|o| game.plays().map(|r| score(game, o, r) as i64).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let mut remaining = match constraints.draws {
+        None => {
+            let limits = supply.iter().map(|&supply| vec![supply; n]).collect::<Vec<_>>();
+            max_weight_flow(&supply, &capacity, &limits, &scores)?.1
+        },
+        Some(draws) => {
+            // Scaling by 2n * lcm(1..=n) puts every breakpoint of the dual on an even penalty, since an
+            // augmenting cycle changes the number of draws by at most n.
+            let lcm = (1..=n as i64).fold(1, |lcm, k| lcm / gcd(lcm, k) * k);
+            let scale = 2 * n as i64 * lcm;
+            let range = scores.iter().flatten().max()? - scores.iter().flatten().min()?;
+            let mut relaxation = Relaxation {
+                scores: &scores,
+                supply: &supply,
+                capacity: &capacity,
+                draws,
+                scale,
+                bound: (2 * n as i64 * scale * (range + 1)) | 1,
+                best: None,
+            };
+            relaxation.branch(vec![0; n], (0..n).map(|o| supply[o].min(capacity[o])).collect());
+            relaxation.best?.1
+        },
     };
 
-    value(your_play) + outcome
+    let responses = opponent_plays.iter()
+        .map(|other_play| {
+            let r = (0..n).find(|&r| remaining[other_play.0][r] > 0)?;
+            remaining[other_play.0][r] -= 1;
+            Some(Play(r))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Optimum {
+        score: opponent_plays.iter().zip(&responses).map(|(&a, &b)| score(game, a, b)).sum(),
+        responses,
+    })
 }
 
 #[aoc(day2, part1)]
 fn part1(input: &[(Play, Strategy)]) -> u32 {
-    input.iter()
-        .map(|(a, b)| (
-            a,
-            match b {
-                Strategy::X => Rock,
-                Strategy::Y => Paper,
-                Strategy::Z => Scissors,
-            }
-        ))
-        .map(|(a, b)| score(*a, b))
-        .sum()
+    total_score(&Game::rock_paper_scissors(), input, Decoding::AsMove)
 }
 
 #[aoc(day2, part2)]
 fn part2(input: &[(Play, Strategy)]) -> u32 {
-    input.iter()
-        .map(|(a, b)| (
-            a,
-            match (a, b) {
-                (Rock, Strategy::X) => Scissors,
-                (Paper, Strategy::X) => Rock,
-                (Scissors, Strategy::X) => Paper,
-                (c, Strategy::Y) => *c,
-                (Rock, Strategy::Z) => Paper,
-                (Paper, Strategy::Z) => Scissors,
-                (Scissors, Strategy::Z) => Rock,
-            }
-        ))
-        .map(|(a, b)| score(*a, b))
-        .sum()
+    total_score(&Game::rock_paper_scissors(), input, Decoding::AsOutcome)
 }
 
 #[cfg(test)]
@@ -119,11 +470,166 @@ mod tests {
 
     #[test]
     fn part1_example1() {
-        assert_eq!(15, part1(&parse(include_str!("../input/2022/day2.part1.test.15.txt"))));
+        assert_eq!(15, part1(&parse(include_str!("../input/2022/day2.part1.test.15.txt")).unwrap()));
+    }
+
+    #[test]
+    fn part1_input() {
+        assert_eq!(12535, part1(&parse(include_str!("../input/2022/day2.txt")).unwrap()));
     }
 
     #[test]
     fn part2_example1() {
-        assert_eq!(12, part2(&parse(include_str!("../input/2022/day2.part2.test.12.txt"))));
+        assert_eq!(12, part2(&parse(include_str!("../input/2022/day2.part2.test.12.txt")).unwrap()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn part2_input() {
+        assert_eq!(15457, part2(&parse(include_str!("../input/2022/day2.txt")).unwrap()));
+    }
+
+    #[test]
+    fn rock_paper_scissors_lizard_spock() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let play = |name: &str| Play(game.moves().iter().position(|m| m.name == name).unwrap());
+
+        for (a, b) in [("Scissors", "Paper"), ("Paper", "Rock"), ("Rock", "Lizard"), ("Lizard", "Spock"), ("Spock", "Scissors"),
+                       ("Scissors", "Lizard"), ("Lizard", "Paper"), ("Paper", "Spock"), ("Spock", "Rock"), ("Rock", "Scissors")] {
+            assert!(game.beats(play(a), play(b)));
+            assert!(!game.beats(play(b), play(a)));
+        }
+    }
+
+    #[test]
+    fn decode_as_outcome() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let guide = parse_guide(&game, "A V\nA W\nA X\nA Y\nA Z").unwrap();
+        let outcomes = guide.iter()
+            .map(|&(a, b)| game.outcome(a, game.decode(a, b, Decoding::AsOutcome)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![Outcome::Loss, Outcome::Loss, Outcome::Draw, Outcome::Win, Outcome::Win], outcomes);
+    }
+
+    #[test]
+    fn game_from_config() {
+        let config = "# name value opponent response\nRock 1 A X\nPaper 2 B Y\nScissors 3 C Z\n";
+        let game = config.parse::<Game>().unwrap();
+        assert_eq!(Game::rock_paper_scissors(), game);
+
+        let guide = parse_guide(&game, include_str!("../input/2022/day2.part1.test.15.txt")).unwrap();
+        assert_eq!(15, total_score(&game, &guide, Decoding::AsMove));
+        assert_eq!(12, total_score(&game, &guide, Decoding::AsOutcome));
+    }
+
+    #[test]
+    fn game_from_invalid_config() {
+        assert!(matches!("Rock 1 A X\nPaper 2 B Y".parse::<Game>(), Err(AocError::InvalidGame(_))));
+        assert!(matches!("Rock 1 A X\nPaper 2 A Y\nScissors 3 C Z".parse::<Game>(), Err(AocError::InvalidGame(_))));
+        assert!(matches!(parse("A Q"), Err(AocError::InvalidStrategy(_))));
+    }
+
+    fn brute_force(game: &Game, opponent_plays: &[Play], constraints: &Constraints) -> Option<u32> {
+        opponent_plays.iter()
+            .map(|_| game.plays())
+            .multi_cartesian_product()
+            .filter(|responses| {
+                let uses = responses.iter().counts();
+                let draws = opponent_plays.iter().zip(responses).filter(|(a, b)| a == b).count();
+
+                constraints.max_uses.iter().enumerate().all(|(i, max)| max.is_none_or(|max| *uses.get(&Play(i)).unwrap_or(&0) <= max))
+                    && constraints.draws.is_none_or(|required| draws == required)
+            })
+            .map(|responses| opponent_plays.iter().zip(&responses).map(|(&a, &b)| score(game, a, b)).sum())
+            .max()
+    }
+
+    #[test]
+    fn optimise_example1() {
+        let game = Game::rock_paper_scissors();
+        let opponent_plays = parse(include_str!("../input/2022/day2.part1.test.15.txt")).unwrap()
+            .iter()
+            .map(|&(a, _)| a)
+            .collect::<Vec<_>>();
+
+        let optimum = optimise(&game, &opponent_plays, &Constraints::default()).unwrap();
+        assert_eq!(24, optimum.score);
+        assert_eq!(vec![Play(1), Play(2), Play(0)], optimum.responses);
+
+        let constraints = Constraints { max_uses: vec![None, None, Some(0)], draws: None };
+        assert_eq!(20, optimise(&game, &opponent_plays, &constraints).unwrap().score);
+
+        let constraints = Constraints { max_uses: vec![], draws: Some(3) };
+        assert_eq!(15, optimise(&game, &opponent_plays, &constraints).unwrap().score);
+
+        let constraints = Constraints { max_uses: vec![Some(0), Some(0), Some(2)], draws: None };
+        assert_eq!(None, optimise(&game, &opponent_plays, &constraints));
+    }
+
+    #[test]
+    fn optimise_matches_brute_force() {
+        let game = Game::rock_paper_scissors();
+        let constraint_sets = [
+            Constraints { max_uses: vec![Some(1), None, Some(2)], draws: None },
+            Constraints { max_uses: vec![], draws: Some(2) },
+            Constraints { max_uses: vec![Some(3), Some(1), Some(2)], draws: Some(1) },
+            Constraints { max_uses: vec![None, Some(0), None], draws: Some(3) },
+        ];
+
+        for opponent_plays in (0..6).map(|_| game.plays()).multi_cartesian_product().step_by(7) {
+            for constraints in &constraint_sets {
+                let optimum = optimise(&game, &opponent_plays, constraints);
+                assert_eq!(brute_force(&game, &opponent_plays, constraints), optimum.as_ref().map(|o| o.score));
+
+                if let Some(optimum) = optimum {
+                    assert_eq!(optimum.score, opponent_plays.iter().zip(&optimum.responses).map(|(&a, &b)| score(&game, a, b)).sum());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn optimise_lizard_spock_matches_brute_force() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let constraint_sets = [
+            Constraints { max_uses: vec![Some(1), None, Some(2), Some(0), Some(1)], draws: Some(2) },
+            Constraints { max_uses: vec![], draws: Some(3) },
+            Constraints { max_uses: vec![Some(2), Some(2), Some(1), Some(1), Some(1)], draws: Some(1) },
+        ];
+
+        for opponent_plays in (0..6).map(|_| game.plays()).multi_cartesian_product().step_by(997) {
+            for constraints in &constraint_sets {
+                let optimum = optimise(&game, &opponent_plays, constraints);
+                assert_eq!(brute_force(&game, &opponent_plays, constraints), optimum.map(|o| o.score));
+            }
+        }
+    }
+
+    #[test]
+    fn optimise_lizard_spock_quickly() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let opponent_plays = (0..2500).map(|i| Play(i * i % 7 % 5)).collect::<Vec<_>>();
+        let constraints = Constraints { max_uses: vec![Some(700), Some(400), None, Some(600), Some(300)], draws: Some(150) };
+
+        let start = std::time::Instant::now();
+        let optimum = optimise(&game, &opponent_plays, &constraints).unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(150, opponent_plays.iter().zip(&optimum.responses).filter(|(a, b)| a == b).count());
+    }
+
+    #[test]
+    fn optimise_input() {
+        let game = Game::rock_paper_scissors();
+        let opponent_plays = parse(include_str!("../input/2022/day2.txt")).unwrap()
+            .iter()
+            .map(|&(a, _)| a)
+            .collect::<Vec<_>>();
+        let constraints = Constraints { max_uses: vec![Some(800), Some(900), Some(900)], draws: Some(500) };
+        let optimum = optimise(&game, &opponent_plays, &constraints).unwrap();
+        let uses = optimum.responses.iter().counts();
+
+        assert_eq!(17702, optimum.score);
+        assert!(uses.values().all(|&count| count <= 900) && uses[&Play(0)] <= 800);
+        assert_eq!(500, opponent_plays.iter().zip(&optimum.responses).filter(|(a, b)| a == b).count());
+    }
+}
@@ -1,47 +1,81 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Itertools;
+use anyhow::{bail, ensure, Context, Result};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Rucksack(pub u64, pub u64);
+
+impl Rucksack {
+    pub fn items(&self) -> u64 {
+        self.0 | self.1
+    }
+
+    pub fn misplaced(&self) -> u64 {
+        self.0 & self.1
+    }
+}
+
+pub fn priority(c: char) -> Result<u32> {
+    match c {
+        'a'..='z' => Ok(c as u32 - 'a' as u32 + 1),
+        'A'..='Z' => Ok(c as u32 - 'A' as u32 + 27),
+        _ => bail!("Unknown character: {}", c),
+    }
+}
+
+pub fn item_mask(items: &str) -> Result<u64> {
+    items
+        .chars()
+        .try_fold(0, |mask, c| Ok(mask | 1 << (priority(c)? - 1)))
+}
+
+pub fn mask_priority(mask: u64) -> u32 {
+    (0..52)
+        .filter(|bit| mask & (1 << bit) != 0)
+        .map(|bit| bit + 1)
+        .sum()
+}
+
+pub fn parse_rucksack(line: &str) -> Result<Rucksack> {
+    if let Some(c) = line.chars().find(|c| !c.is_ascii_alphabetic()) {
+        bail!("Unknown character: {}", c);
+    }
+    ensure!(line.len().is_multiple_of(2), "Compartments differ in size: {}", line);
+    let (a, b) = line.split_at(line.len() / 2);
+
+    Ok(Rucksack(item_mask(a)?, item_mask(b)?))
+}
 
 #[aoc_generator(day3)]
-fn parse(input: &str) -> Vec<String> {
+fn parse(input: &str) -> Result<Vec<Rucksack>> {
     input
         .lines()
-        .map(str::to_string)
+        .enumerate()
+        .map(|(i, line)| parse_rucksack(line).with_context(|| format!("Invalid rucksack on line {}", i + 1)))
         .collect()
 }
 
-fn priority(c: char) -> u32 {
-    let ord: u32 = c.into();
+pub fn group_priorities(input: &[Rucksack], group_size: usize) -> Result<u32> {
+    ensure!(group_size > 0, "Group size must be positive");
+    ensure!(input.len().is_multiple_of(group_size), "{} rucksacks do not form groups of {}", input.len(), group_size);
 
-    match ord {
-        97..=122 => ord - 97 + 1,
-        65..=90  => ord - 65 + 27,
-        _ => panic!("Unknown character: {}", c),
-    }
+    Ok(input
+        .chunks_exact(group_size)
+        .map(|group| group.iter().fold(u64::MAX, |common, rucksack| common & rucksack.items()))
+        .map(mask_priority)
+        .sum())
 }
 
 #[aoc(day3, part1)]
-fn part1(input: &[String]) -> u32 {
+fn part1(input: &[Rucksack]) -> u32 {
     input.iter()
-        .map(|line| (&line[0..(line.len() / 2)], &line[(line.len() / 2)..]))
-        .filter_map(|(a, b)| {
-            a.chars()
-                .find(|ac| b.chars().contains(ac))
-        })
-        .map(priority)
+        .map(Rucksack::misplaced)
+        .map(mask_priority)
         .sum()
 }
 
 #[aoc(day3, part2)]
-fn part2(input: &[String]) -> u32 {
-    input.iter()
-        .tuples()
-        .filter_map(|(a, b, c)| {
-            a.chars()
-                .filter(|candidate| b.chars().contains(candidate))
-                .find(|candidate| c.chars().contains(candidate))
-        })
-        .map(priority)
-        .sum()
+fn part2(input: &[Rucksack]) -> Result<u32> {
+    group_priorities(input, 3)
 }
 
 #[cfg(test)]
@@ -50,11 +84,40 @@ mod tests {
 
     #[test]
     fn part1_example1() {
-        assert_eq!(157, part1(&parse(include_str!("../input/2022/day3.part1.test.157.txt"))));
+        assert_eq!(157, part1(&parse(include_str!("../input/2022/day3.part1.test.157.txt")).unwrap()));
     }
 
     #[test]
     fn part2_example1() {
-        assert_eq!(70, part2(&parse(include_str!("../input/2022/day3.part2.test.70.txt"))));
+        assert_eq!(70, part2(&parse(include_str!("../input/2022/day3.part2.test.70.txt")).unwrap()).unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn group_priorities_example1() {
+        let input = parse(include_str!("../input/2022/day3.part2.test.70.txt")).unwrap();
+        assert_eq!(2278, group_priorities(&input, 1).unwrap());
+        assert_eq!(371, group_priorities(&input, 2).unwrap());
+        assert_eq!(0, group_priorities(&input, 6).unwrap());
+        assert!(group_priorities(&input, 0).is_err());
+    }
+
+    #[test]
+    fn group_priorities_incomplete_group() {
+        let input = parse(include_str!("../input/2022/day3.part2.test.70.txt")).unwrap();
+        let error = group_priorities(&input[..4], 3).unwrap_err();
+        assert_eq!("4 rucksacks do not form groups of 3", error.to_string());
+    }
+
+    #[test]
+    fn parse_unknown_character() {
+        let error = parse("vJrwpWtwJgWr\nhcsFMM-fFFhF").unwrap_err();
+        assert_eq!("Invalid rucksack on line 2", error.to_string());
+        assert_eq!("Unknown character: -", error.root_cause().to_string());
+    }
+
+    #[test]
+    fn parse_non_ascii() {
+        let error = parse("aéb").unwrap_err();
+        assert_eq!("Unknown character: é", error.root_cause().to_string());
+    }
+}
@@ -1,7 +1,8 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
+use crate::interval::Interval;
 
-type RangePair = ((u32, u32), (u32, u32));
+type RangePair = (Interval, Interval);
 
 #[aoc_generator(day4)]
 fn parse(input: &str) -> Vec<RangePair> {
@@ -11,25 +12,47 @@ fn parse(input: &str) -> Vec<RangePair> {
         .flat_map(|range| range.split('-'))
         .map(|num| num.parse().unwrap())
         .tuples()
-        .map(|(a, b, c, d)| ((a, b), (c, d)))
+        .map(|(a, b, c, d)| (Interval::closed(a, b).unwrap(), Interval::closed(c, d).unwrap()))
         .collect()
 }
 
+pub fn overlapping_assignments(input: &[RangePair]) -> Vec<(usize, usize)> {
+    let assignments = input.iter()
+        .enumerate()
+        .flat_map(|(i, (a, b))| [((i, 0), a), ((i, 1), b)])
+        .sorted_by_key(|(_, interval)| (interval.start, interval.end))
+        .collect::<Vec<_>>();
+    let mut overlapping = vec![];
+    let mut furthest: Option<((usize, usize), i64)> = None;
+
+    for (id, interval) in assignments {
+        match furthest {
+            Some((other, end)) if interval.start < end => {
+                overlapping.push(id);
+                overlapping.push(other);
+
+                if interval.end > end {
+                    furthest = Some((id, interval.end));
+                }
+            },
+            _ => furthest = Some((id, interval.end)),
+        }
+    }
+
+    overlapping.into_iter().sorted().dedup().collect()
+}
+
 #[aoc(day4, part1)]
 fn part1(input: &[RangePair]) -> usize {
     input.iter()
-        .filter(|((a1, b1), (a2, b2))| (a2 >= a1 && b2 <= b1) || (a1 >= a2 && b1 <= b2))
+        .filter(|(a, b)| a.contains(b) || b.contains(a))
         .count()
 }
 
 #[aoc(day4, part2)]
 fn part2(input: &[RangePair]) -> usize {
     input.iter()
-        .map(|((a1, b1), (a2, b2))| match a1 <= a2 {
-            true => ((a1, b1), (a2, b2)),
-            false => ((a2, b2), (a1, b1)),
-        })
-        .filter(|((_, b1), (a2, _))| a2 <= b1)
+        .filter(|(a, b)| a.overlaps(b))
         .count()
 }
 
@@ -46,4 +69,11 @@ mod tests {
     fn part2_example1() {
         assert_eq!(4, part2(&parse(include_str!("../input/2022/day4.part2.test.4.txt"))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn overlapping_assignments_example1() {
+        let expected = (0..6).cartesian_product(0..2).collect::<Vec<_>>();
+        assert_eq!(expected, overlapping_assignments(&parse(include_str!("../input/2022/day4.part2.test.4.txt"))));
+        assert_eq!(vec![(0, 1), (1, 1)], overlapping_assignments(&parse("1-2,5-6\n3-4,6-7\n8-9,10-12")));
+    }
+}
@@ -1,75 +1,293 @@
-use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
 use aoc_runner_derive::{aoc, aoc_generator};
+use anyhow::{bail, ensure, Context, Result};
+use itertools::Itertools;
 use scan_fmt::scan_fmt;
 
-type Input = (Vec<Vec<char>>, Vec<(usize, usize, usize)>);
+pub type Crate = String;
+pub type Stacks = Vec<Vec<Crate>>;
+type Input = (Stacks, Vec<Move>);
 
-#[aoc_generator(day5)]
-fn parse(input: &str) -> Input {
-    let (stacks_input, moves_input) = input.split_once("\n\n").unwrap();
-
-    (
-        parse_stacks(stacks_input),
-        parse_moves(moves_input)
-    )
-}
-
-fn parse_stacks(stacks_input: &str) -> Vec<Vec<char>> {
-    let mut stacks_input: VecDeque<&str> = stacks_input.lines().rev().collect();
-    let stacks_header = stacks_input.pop_front().unwrap();
-    let num_stacks = stacks_header.chars()
-        .filter_map(|c| c.to_digit(10))
-        .count();
-    let mut stacks: Vec<Vec<char>> = vec![vec![]; num_stacks];
-
-    for line in stacks_input {
-        for (stack, c) in line.chars().skip(1).step_by(4).enumerate() {
-            if c.is_ascii_alphabetic() {
-                stacks[stack].push(c);
-            }
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Move {
+    pub count: usize,
+    pub source: usize,
+    pub target: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CraneError {
+    UnknownStack { move_index: usize, stack: usize },
+    NotEnoughCrates { move_index: usize, stack: usize, requested: usize, available: usize },
+}
+
+impl fmt::Display for CraneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CraneError::UnknownStack { move_index, stack } =>
+                write!(f, "Move {} refers to unknown stack {}", move_index + 1, stack + 1),
+            CraneError::NotEnoughCrates { move_index, stack, requested, available } =>
+                write!(f, "Move {} takes {} crates from stack {}, which only holds {}", move_index + 1, requested, stack + 1, available),
         }
     }
+}
 
-    stacks
+impl std::error::Error for CraneError {}
+
+pub trait Crane {
+    fn place(&self, lifted: Vec<Crate>, target: &mut Vec<Crate>);
 }
 
-fn parse_moves(moves_input: &str) -> Vec<(usize, usize, usize)> {
-    moves_input.lines().into_iter()
-        .map(|line| scan_fmt!(line, "move {d} from {d} to {d}", usize, usize, usize).unwrap())
-        .map(|(a, b, c)| (a, b - 1, c - 1))
-        .collect()
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn place(&self, lifted: Vec<Crate>, target: &mut Vec<Crate>) {
+        target.extend(lifted.into_iter().rev());
+    }
 }
 
-#[aoc(day5, part1)]
-fn part1((stacks, moves): &Input) -> String {
-    let mut stacks = stacks.clone();
+pub struct CrateMover9001;
 
-    for &(count, source, target) in moves {
-        for _ in 0..count {
-            let temp = stacks[source].pop().unwrap();
-            stacks[target].push(temp);
+impl Crane for CrateMover9001 {
+    fn place(&self, lifted: Vec<Crate>, target: &mut Vec<Crate>) {
+        target.extend(lifted);
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Drawing {
+    pub stacks: Stacks,
+    crate_starts: Vec<usize>,
+    label_starts: Vec<usize>,
+    width: usize,
+    padded: bool,
+}
+
+impl Drawing {
+    pub fn new(stacks: Stacks) -> Drawing {
+        let label_width = stacks.len().to_string().len();
+        let crate_width = stacks.iter().flatten().map(|c| c.chars().count()).max().unwrap_or(1);
+        let cell_width = crate_width.max(label_width) + 2;
+        let crate_starts = (0..stacks.len()).map(|i| i * (cell_width + 1)).collect::<Vec<_>>();
+        let label_starts = crate_starts.iter()
+            .enumerate()
+            .map(|(i, start)| start + (cell_width - (i + 1).to_string().len()) / 2)
+            .collect();
+
+        Drawing {
+            width: (stacks.len() * (cell_width + 1)).saturating_sub(1),
+            stacks,
+            crate_starts,
+            label_starts,
+            padded: true,
         }
     }
 
-    stacks.into_iter()
-        .map(|stack| *stack.last().unwrap())
+    pub fn with_stacks(&self, stacks: Stacks) -> Drawing {
+        Drawing { stacks, ..self.clone() }
+    }
+}
+
+fn place(line: &mut Vec<char>, start: usize, text: &str) {
+    for (i, c) in text.chars().enumerate() {
+        if start + i >= line.len() {
+            line.resize(start + i + 1, ' ');
+        }
+        line[start + i] = c;
+    }
+}
+
+impl fmt::Display for Drawing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self.stacks.iter().map(Vec::len).max().unwrap_or_default();
+
+        for level in (0..height).rev() {
+            let mut line = vec![' '; self.width];
+            for (stack, &start) in self.stacks.iter().zip(&self.crate_starts) {
+                if let Some(c) = stack.get(level) {
+                    place(&mut line, start, &format!("[{}]", c));
+                }
+            }
+
+            let line = line.iter().collect::<String>();
+            writeln!(f, "{}", if self.padded { &line } else { line.trim_end() })?;
+        }
+
+        let mut header = vec![' '; self.width];
+        for (i, &start) in self.label_starts.iter().enumerate() {
+            place(&mut header, start, &(i + 1).to_string());
+        }
+
+        write!(f, "{}", header.iter().collect::<String>())
+    }
+}
+
+impl FromStr for Drawing {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Drawing> {
+        let lines = s.lines().map(|line| line.chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+        let (header, rows) = lines.split_last().context("Empty stack drawing")?;
+
+        let label_spans = header.iter()
+            .enumerate()
+            .group_by(|(_, c)| c.is_whitespace())
+            .into_iter()
+            .filter(|(whitespace, _)| !whitespace)
+            .map(|(_, group)| {
+                let group = group.collect::<Vec<_>>();
+                (group[0].0, group[0].0 + group.len(), group.iter().map(|(_, c)| *c).collect::<String>())
+            })
+            .collect::<Vec<_>>();
+        ensure!(!label_spans.is_empty(), "line {}: missing stack labels", lines.len());
+
+        for (i, (start, _, label)) in label_spans.iter().enumerate() {
+            ensure!(*label == (i + 1).to_string(), "line {}, column {}: expected stack label {}, found {}", lines.len(), start + 1, i + 1, label);
+        }
+
+        let mut crate_starts: Vec<Option<usize>> = vec![None; label_spans.len()];
+        let mut columns: Vec<Vec<(usize, Crate)>> = vec![vec![]; label_spans.len()];
+
+        for (row_index, row) in rows.iter().enumerate() {
+            let line_number = row_index + 1;
+            let mut i = 0;
+
+            while i < row.len() {
+                match row[i] {
+                    ' ' => i += 1,
+                    '[' => {
+                        let end = (i..row.len())
+                            .find(|&j| row[j] == ']')
+                            .with_context(|| format!("line {}, column {}: unclosed crate", line_number, i + 1))?;
+                        let label = row[(i + 1)..end].iter().collect::<String>();
+                        ensure!(!label.is_empty() && !label.contains(|c: char| c.is_whitespace() || c == '['),
+                            "line {}, column {}: invalid crate label [{}]", line_number, i + 1, label);
+
+                        let stack = label_spans.iter()
+                            .positions(|&(start, label_end, _)| start <= end && i < label_end)
+                            .exactly_one()
+                            .map_err(|_| anyhow::anyhow!("line {}, column {}: crate [{}] is not aligned with a single stack label", line_number, i + 1, label))?;
+
+                        match crate_starts[stack] {
+                            Some(start) if start != i => bail!("line {}, column {}: crate [{}] is not aligned with the other crates in stack {}", line_number, i + 1, label, stack + 1),
+                            _ => crate_starts[stack] = Some(i),
+                        }
+
+                        columns[stack].push((row_index, label));
+                        i = end + 1;
+                    },
+                    c => bail!("line {}, column {}: unexpected character '{}'", line_number, i + 1, c),
+                }
+            }
+        }
+
+        let padded = if rows.iter().all(|row| row.len() == header.len()) {
+            true
+        } else if let Some(row_index) = rows.iter().position(|row| row.last() == Some(&' ')) {
+            bail!("line {}: inconsistent trailing whitespace", row_index + 1);
+        } else {
+            false
+        };
+
+        for (stack, column) in columns.iter().enumerate() {
+            for (k, (row_index, label)) in column.iter().enumerate() {
+                ensure!(*row_index == rows.len() - column.len() + k, "line {}: crate [{}] in stack {} is floating", row_index + 1, label, stack + 1);
+            }
+        }
+
+        ensure!(rows.is_empty() || columns.iter().any(|column| column.len() == rows.len()), "line 1: empty row");
+
+        let offset = crate_starts.iter()
+            .zip(&label_spans)
+            .find_map(|(crate_start, (label_start, _, _))| crate_start.map(|crate_start| *label_start as isize - crate_start as isize))
+            .unwrap_or(1);
+
+        Ok(Drawing {
+            stacks: columns.into_iter()
+                .map(|column| column.into_iter().rev().map(|(_, label)| label).collect())
+                .collect(),
+            crate_starts: crate_starts.iter()
+                .zip(&label_spans)
+                .map(|(crate_start, (label_start, _, _))| crate_start.unwrap_or((*label_start as isize - offset).max(0) as usize))
+                .collect(),
+            label_starts: label_spans.iter().map(|(start, _, _)| *start).collect(),
+            width: header.len(),
+            padded,
+        })
+    }
+}
+
+#[aoc_generator(day5)]
+fn parse(input: &str) -> Result<Input> {
+    let (stacks_input, moves_input) = input.split_once("\n\n").context("Missing blank line after stack drawing")?;
+
+    Ok((
+        stacks_input.parse::<Drawing>()?.stacks,
+        parse_moves(moves_input)?
+    ))
+}
+
+fn parse_moves(moves_input: &str) -> Result<Vec<Move>> {
+    moves_input.lines()
+        .map(|line| {
+            let (count, source, target) = scan_fmt!(line, "move {d} from {d} to {d}", usize, usize, usize)?;
+
+            Ok(Move {
+                count,
+                source: source.checked_sub(1).with_context(|| format!("Invalid stack in move: {}", line))?,
+                target: target.checked_sub(1).with_context(|| format!("Invalid stack in move: {}", line))?,
+            })
+        })
         .collect()
 }
 
-#[aoc(day5, part2)]
-fn part2((stacks, moves): &Input) -> String {
+pub fn apply_move(crane: &dyn Crane, stacks: &mut Stacks, move_index: usize, m: &Move) -> Result<(), CraneError> {
+    for stack in [m.source, m.target] {
+        if stack >= stacks.len() {
+            return Err(CraneError::UnknownStack { move_index, stack });
+        }
+    }
+
+    let available = stacks[m.source].len();
+    if m.count > available {
+        return Err(CraneError::NotEnoughCrates { move_index, stack: m.source, requested: m.count, available });
+    }
+
+    let lifted = stacks[m.source].split_off(available - m.count);
+    crane.place(lifted, &mut stacks[m.target]);
+
+    Ok(())
+}
+
+pub fn run(crane: &dyn Crane, stacks: &Stacks, moves: &[Move]) -> Result<Stacks, CraneError> {
     let mut stacks = stacks.clone();
-    let mut buffer = Vec::new();
 
-    for &(count, source, target) in moves {
-        let source_offset = stacks[source].len() - count;
-        buffer.extend(stacks[source].drain(source_offset..));
-        stacks[target].extend(buffer.drain(0..));
+    for (move_index, m) in moves.iter().enumerate() {
+        apply_move(crane, &mut stacks, move_index, m)?;
     }
 
-    stacks.into_iter()
-        .map(|stack| *stack.last().unwrap())
-        .collect()
+    Ok(stacks)
+}
+
+pub fn render(stacks: &Stacks) -> String {
+    Drawing::new(stacks.clone()).to_string()
+}
+
+fn top_crates(stacks: &Stacks) -> String {
+    stacks.iter()
+        .filter_map(|stack| stack.last())
+        .join("")
+}
+
+#[aoc(day5, part1)]
+fn part1((stacks, moves): &Input) -> Result<String, CraneError> {
+    Ok(top_crates(&run(&CrateMover9000, stacks, moves)?))
+}
+
+#[aoc(day5, part2)]
+fn part2((stacks, moves): &Input) -> Result<String, CraneError> {
+    Ok(top_crates(&run(&CrateMover9001, stacks, moves)?))
 }
 
 #[cfg(test)]
@@ -78,11 +296,82 @@ mod tests {
 
     #[test]
     fn part1_example1() {
-        assert_eq!("CMZ", part1(&parse(include_str!("../input/2022/day5.part1.test.CMZ.txt"))));
+        assert_eq!("CMZ", part1(&parse(include_str!("../input/2022/day5.part1.test.CMZ.txt")).unwrap()).unwrap());
     }
 
     #[test]
     fn part2_example1() {
-        assert_eq!("MCD", part2(&parse(include_str!("../input/2022/day5.part2.test.MCD.txt"))));
+        assert_eq!("MCD", part2(&parse(include_str!("../input/2022/day5.part2.test.MCD.txt")).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn render_after_each_step_example1() {
+        let (stacks, moves) = parse(include_str!("../input/2022/day5.part1.test.CMZ.txt")).unwrap();
+        let drawing = include_str!("../input/2022/day5.part1.test.CMZ.txt").split_once("\n\n").unwrap().0;
+        assert_eq!(drawing, render(&stacks));
+
+        let expected = [
+            "[D]        \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 ",
+            "        [Z]\n        [N]\n    [C] [D]\n    [M] [P]\n 1   2   3 ",
+        ];
+        for (steps, expected) in (1..=2).zip(expected) {
+            assert_eq!(expected, render(&run(&CrateMover9000, &stacks, &moves[..steps]).unwrap()));
+        }
+    }
+
+    #[test]
+    fn illegal_move() {
+        let (stacks, mut moves) = parse(include_str!("../input/2022/day5.part1.test.CMZ.txt")).unwrap();
+        moves[2].count = 4;
+
+        let expected = CraneError::NotEnoughCrates { move_index: 2, stack: 1, requested: 4, available: 2 };
+        assert_eq!(Err(expected.clone()), part1(&(stacks.clone(), moves.clone())));
+        assert_eq!("Move 3 takes 4 crates from stack 2, which only holds 2", expected.to_string());
+
+        moves[0].target = 3;
+        assert_eq!(Err(CraneError::UnknownStack { move_index: 0, stack: 3 }), part2(&(stacks, moves)));
+    }
+
+    #[test]
+    fn drawing_round_trip_example1() {
+        let drawing = include_str!("../input/2022/day5.part1.test.CMZ.txt").split_once("\n\n").unwrap().0;
+        assert_eq!(drawing, drawing.parse::<Drawing>().unwrap().to_string());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn drawing_round_trip_wide() {
+        let drawing = [
+            "                                       [K]",
+            "[AB]                                   [J]",
+            "[CD]    [E] [F] [G] [H] [I]    [Q]     [XY]",
+            " 1   2   3   4   5   6   7   8   9  10  11",
+        ].join("\n");
+        let parsed = drawing.parse::<Drawing>().unwrap();
+
+        assert_eq!(vec!["CD".to_string(), "AB".to_string()], parsed.stacks[0]);
+        assert_eq!(vec!["XY".to_string(), "J".to_string(), "K".to_string()], parsed.stacks[10]);
+        assert_eq!(drawing, parsed.to_string());
+
+        let mut stacks = parsed.stacks.clone();
+        apply_move(&CrateMover9001, &mut stacks, 0, &Move { count: 2, source: 10, target: 1 }).unwrap();
+        let expected = [
+            "[AB][K]",
+            "[CD][J] [E] [F] [G] [H] [I]    [Q]     [XY]",
+            " 1   2   3   4   5   6   7   8   9  10  11",
+        ];
+        assert_eq!(expected.join("\n"), parsed.with_stacks(stacks).to_string());
+    }
+
+    #[test]
+    fn drawing_misaligned() {
+        assert!("[A] \n[B]\n 1 ".parse::<Drawing>().is_err());
+        assert!(" [A]\n[B] \n 1 ".parse::<Drawing>().is_err());
+        assert!("[A]    \n    [B]\n 1   2 ".parse::<Drawing>().is_err());
+        assert!("[A] [B]\n 1   3 ".parse::<Drawing>().is_err());
+        assert!("[A] x  \n 1   2 ".parse::<Drawing>().is_err());
+        assert_eq!(
+            "line 1, column 3: crate [A] is not aligned with a single stack label",
+            "  [A]   \n 1   2 ".parse::<Drawing>().unwrap_err().to_string()
+        );
+    }
+}
@@ -1,36 +1,87 @@
+use std::collections::VecDeque;
+use std::io::{self, BufReader, Bytes, Read};
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Itertools;
 
 #[aoc_generator(day6)]
-fn parse(input: &str) -> Vec<char> {
-    input.chars().collect()
+fn parse(input: &str) -> Vec<u8> {
+    input.as_bytes().to_vec()
 }
 
-fn solve(input: &[char], length: usize) -> Option<usize> {
-    input
-        .windows(length)
-        .enumerate()
-        .find_map(|(i, w)| {
-            if w.iter().tuple_combinations().all(|(a,b)| a != b) {
-                Some(i + length)
-            } else {
-                None
+pub struct Markers<R> {
+    bytes: Bytes<BufReader<R>>,
+    length: usize,
+    window: VecDeque<u8>,
+    counts: [usize; 256],
+    distinct: usize,
+    position: usize,
+}
+
+impl<R: Read> Iterator for Markers<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for byte in self.bytes.by_ref() {
+            let byte = match byte {
+                Ok(byte) => byte,
+                Err(error) => return Some(Err(error)),
+            };
+
+            self.position += 1;
+            self.window.push_back(byte);
+            self.counts[byte as usize] += 1;
+            if self.counts[byte as usize] == 1 {
+                self.distinct += 1;
             }
-        })
+
+            if self.window.len() > self.length {
+                let old = self.window.pop_front().unwrap() as usize;
+                self.counts[old] -= 1;
+                if self.counts[old] == 0 {
+                    self.distinct -= 1;
+                }
+            }
+
+            if self.window.len() == self.length && self.distinct == self.length {
+                return Some(Ok(self.position));
+            }
+        }
+
+        None
+    }
+}
+
+pub fn markers<R: Read>(reader: R, length: usize) -> io::Result<Markers<R>> {
+    if length == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Marker length must be positive"));
+    }
+
+    Ok(Markers {
+        bytes: BufReader::new(reader).bytes(),
+        length,
+        window: VecDeque::with_capacity(length + 1),
+        counts: [0; 256],
+        distinct: 0,
+        position: 0,
+    })
+}
+
+fn solve(input: &[u8], length: usize) -> Option<usize> {
+    markers(input, length).ok()?.next()?.ok()
 }
 
 #[aoc(day6, part1)]
-fn part1(input: &[char]) -> usize {
+fn part1(input: &[u8]) -> usize {
     solve(input, 4).unwrap()
 }
 
 #[aoc(day6, part2)]
-fn part2(input: &[char]) -> usize {
+fn part2(input: &[u8]) -> usize {
     solve(input, 14).unwrap()
 }
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
     use super::*;
 
     #[test]
@@ -82,4 +133,25 @@ mod tests {
     fn part2_example5() {
         assert_eq!(26, part2(&parse("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw")));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn markers_match_brute_force() {
+        let input = b"mjqjpqmgbljsphdztnvjfqwrcgsmlbbvwbjplbgvbhsrlpgdmjqwftvnczzcfzfwzzqfrljwzlrfnpqdbhtmscgvjw";
+
+        for length in 1..=14 {
+            let expected = input
+                .windows(length)
+                .enumerate()
+                .filter(|(_, w)| w.iter().tuple_combinations().all(|(a, b)| a != b))
+                .map(|(i, _)| i + length)
+                .collect::<Vec<_>>();
+
+            assert_eq!(expected, markers(&input[..], length).unwrap().collect::<io::Result<Vec<_>>>().unwrap());
+        }
+    }
+
+    #[test]
+    fn markers_zero_length() {
+        assert_eq!(io::ErrorKind::InvalidInput, markers(&b"abc"[..], 0).err().unwrap().kind());
+    }
+}
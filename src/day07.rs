@@ -1,13 +1,61 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
 use std::str::FromStr;
 use aoc_runner_derive::{aoc, aoc_generator};
+use anyhow::Context;
 use itertools::Itertools;
 use crate::day07::Line::{CommandCd, CommandLs, Dir, File};
 
-#[derive(Debug)]
-enum AocError {}
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AocError {
+    UnexpectedInput(String),
+    Inconsistent(Vec<Diagnostic>),
+}
+
+impl fmt::Display for AocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AocError::UnexpectedInput(s) => write!(f, "Unexpected input: {}", s),
+            AocError::Inconsistent(diagnostics) => {
+                write!(f, "Inconsistent transcript:")?;
+                diagnostics.iter().try_for_each(|diagnostic| write!(f, "\n{}", diagnostic))
+            },
+        }
+    }
+}
+
+impl std::error::Error for AocError {}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Issue {
+    ConflictingListing { path: String, first_line: usize },
+    UnlistedDirectory { path: String },
+    AboveRoot,
+    OutputWithoutLs,
+}
 
-#[derive(Debug)]
-enum Line {
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub issue: Issue,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+
+        match &self.issue {
+            Issue::ConflictingListing { path, first_line } => write!(f, "listing of {} differs from the one on line {}", path, first_line),
+            Issue::UnlistedDirectory { path } => write!(f, "cd into {}, which was never listed", path),
+            Issue::AboveRoot => write!(f, "cd .. above the root directory"),
+            Issue::OutputWithoutLs => write!(f, "listing output without a preceding ls"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Line {
     CommandCd(String),
     CommandLs,
     Dir(String),
@@ -24,101 +72,302 @@ impl FromStr for Line {
             ["$", "cd", name] => Ok(CommandCd(name.to_string())),
             ["$", "ls"] => Ok(CommandLs),
             ["dir", name] => Ok(Dir(name.to_string())),
-            [size, name] => Ok(File(name.to_string(), size.parse().unwrap())),
-            _ => panic!("Unexpected input: {}", s),
+            [size, name] => Ok(File(name.to_string(), size.parse().map_err(|_| AocError::UnexpectedInput(s.to_string()))?)),
+            _ => Err(AocError::UnexpectedInput(s.to_string())),
         }
     }
 }
 
 #[aoc_generator(day7)]
-fn parse(input: &str) -> Vec<Line> {
+pub fn parse(input: &str) -> anyhow::Result<Vec<Line>> {
     input
         .lines()
-        .map(&str::parse)
-        .collect::<Result<_, _>>()
-        .unwrap()
+        .enumerate()
+        .map(|(i, line)| line.parse().with_context(|| format!("Invalid transcript line {}", i + 1)))
+        .collect()
 }
 
-fn basename(name: &str) -> String {
-    let skips = if name.ends_with('/') {
-        2
-    } else {
-        1
-    };
+pub const ROOT: usize = 0;
+const DISK_SIZE: usize = 70000000;
+const REQUIRED_FREE: usize = 30000000;
+
+#[derive(Debug, Clone, Default)]
+pub struct Directory {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub children: BTreeMap<String, usize>,
+    pub files: BTreeMap<String, usize>,
+    pub size: usize,
+}
 
-    let parts = name.split('/').collect::<Vec<_>>();
-    parts.iter().take(parts.len() - skips).join("/")
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    pub dirs: Vec<Directory>,
 }
 
-fn path_join(cwd: &str, target: &str) -> String {
-    match target {
-        ".." => basename(cwd),
-        _ => format!("{}{}", cwd, target),
+impl FileSystem {
+    fn new() -> FileSystem {
+        FileSystem {
+            dirs: vec![Directory { name: "/".to_string(), ..Directory::default() }],
+        }
+    }
+
+    fn child(&mut self, dir: usize, name: &str) -> usize {
+        if let Some(&child) = self.dirs[dir].children.get(name) {
+            return child;
+        }
+
+        self.dirs.push(Directory { name: name.to_string(), parent: Some(dir), ..Directory::default() });
+        let child = self.dirs.len() - 1;
+        self.dirs[dir].children.insert(name.to_string(), child);
+
+        child
+    }
+
+    fn compute_sizes(&mut self) {
+        // Children are always created after their parent, so a reverse scan is a post-order traversal
+        for dir in (0..self.dirs.len()).rev() {
+            self.dirs[dir].size += self.dirs[dir].files.values().sum::<usize>();
+
+            if let Some(parent) = self.dirs[dir].parent {
+                self.dirs[parent].size += self.dirs[dir].size;
+            }
+        }
+    }
+
+    pub fn resolve(&self, path: &str) -> Option<usize> {
+        path.split('/')
+            .filter(|part| !part.is_empty())
+            .try_fold(ROOT, |dir, part| match part {
+                "." => Some(dir),
+                ".." => Some(self.dirs[dir].parent.unwrap_or(ROOT)),
+                _ => self.dirs[dir].children.get(part).copied(),
+            })
+    }
+
+    pub fn du(&self, path: &str) -> Option<usize> {
+        self.resolve(path).map(|dir| self.dirs[dir].size)
+    }
+
+    pub fn path(&self, dir: usize) -> String {
+        match self.dirs[dir].parent {
+            None => "/".to_string(),
+            Some(ROOT) => format!("/{}", self.dirs[dir].name),
+            Some(parent) => format!("{}/{}", self.path(parent), self.dirs[dir].name),
+        }
+    }
+
+    pub fn dir_sizes(&self) -> impl Iterator<Item=usize> + '_ {
+        self.dirs.iter().map(|dir| dir.size)
     }
 }
 
-fn parse_lines(lines: &[Line]) -> (Vec<(String, usize)>, Vec<String>) {
-    let mut cwd = "/".to_string();
-    let mut files = vec![];
-    let mut dirs = vec![];
+pub fn build_file_system(lines: &[Line]) -> FileSystem {
+    let mut file_system = FileSystem::new();
+    let mut cwd = ROOT;
 
-    for line in lines.iter().skip(1) {
+    for line in lines {
         match line {
             CommandCd(target) => {
-                cwd = format!("{}/", path_join(&cwd, target));
+                cwd = match target.as_str() {
+                    "/" => ROOT,
+                    ".." => file_system.dirs[cwd].parent.unwrap_or(ROOT),
+                    name => file_system.child(cwd, name),
+                };
             },
             CommandLs => {},
             Dir(name) => {
-                dirs.push(format!("{}/", path_join(&cwd, name)));
+                file_system.child(cwd, name);
             },
             File(name, size) => {
-                files.push((path_join(&cwd, name), *size));
+                file_system.dirs[cwd].files.insert(name.clone(), *size);
             }
         }
     }
 
-    (files, dirs)
+    file_system.compute_sizes();
+    file_system
+}
+
+type Listing<'a> = (usize, usize, BTreeSet<&'a Line>);
+
+fn finish_listing<'a>(
+    listing: Option<Listing<'a>>,
+    listings: &mut HashMap<usize, (usize, BTreeSet<&'a Line>)>,
+    file_system: &FileSystem,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let Some((dir, line, entries)) = listing else {
+        return;
+    };
+
+    match listings.get(&dir) {
+        Some((first_line, first_entries)) if *first_entries != entries => diagnostics.push(Diagnostic {
+            line,
+            issue: Issue::ConflictingListing { path: file_system.path(dir), first_line: *first_line },
+        }),
+        Some(_) => {},
+        None => {
+            listings.insert(dir, (line, entries));
+        },
+    }
+}
+
+pub fn validate(lines: &[Line]) -> Vec<Diagnostic> {
+    let mut file_system = FileSystem::new();
+    let mut listings: HashMap<usize, (usize, BTreeSet<&Line>)> = HashMap::new();
+    let mut listing: Option<Listing> = None;
+    let mut listed = HashSet::from([ROOT]);
+    let mut diagnostics = vec![];
+    let mut cwd = ROOT;
+
+    for (i, line) in lines.iter().enumerate() {
+        let line_number = i + 1;
+
+        match line {
+            CommandCd(target) => {
+                finish_listing(listing.take(), &mut listings, &file_system, &mut diagnostics);
+
+                cwd = match target.as_str() {
+                    "/" => ROOT,
+                    ".." => file_system.dirs[cwd].parent.unwrap_or_else(|| {
+                        diagnostics.push(Diagnostic { line: line_number, issue: Issue::AboveRoot });
+                        ROOT
+                    }),
+                    name => {
+                        let child = file_system.child(cwd, name);
+
+                        if !listed.contains(&child) {
+                            diagnostics.push(Diagnostic { line: line_number, issue: Issue::UnlistedDirectory { path: file_system.path(child) } });
+                        }
+
+                        child
+                    },
+                };
+            },
+            CommandLs => {
+                finish_listing(listing.take(), &mut listings, &file_system, &mut diagnostics);
+                listing = Some((cwd, line_number, BTreeSet::new()));
+            },
+            Dir(_) | File(_, _) => {
+                let Some((_, _, entries)) = listing.as_mut() else {
+                    diagnostics.push(Diagnostic { line: line_number, issue: Issue::OutputWithoutLs });
+                    continue;
+                };
+
+                entries.insert(line);
+
+                if let Dir(name) = line {
+                    listed.insert(file_system.child(cwd, name));
+                }
+            },
+        }
+    }
+
+    finish_listing(listing, &mut listings, &file_system, &mut diagnostics);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    diagnostics
+}
+
+pub fn build_file_system_strict(lines: &[Line]) -> Result<FileSystem, AocError> {
+    let diagnostics = validate(lines);
+
+    if !diagnostics.is_empty() {
+        return Err(AocError::Inconsistent(diagnostics));
+    }
+
+    Ok(build_file_system(lines))
+}
+
+pub fn render_tree(file_system: &FileSystem) -> String {
+    let mut output = String::new();
+    render_dir(file_system, ROOT, 0, &mut output);
+    output
+}
+
+fn render_dir(file_system: &FileSystem, dir: usize, depth: usize, output: &mut String) {
+    let directory = &file_system.dirs[dir];
+    output.push_str(&format!("{}- {} (dir, size={})\n", "  ".repeat(depth), directory.name, directory.size));
+
+    let entries = directory.children.iter()
+        .map(|(name, &child)| (name, Some(child), 0))
+        .merge_by(directory.files.iter().map(|(name, &size)| (name, None, size)), |a, b| a.0 <= b.0);
+
+    for (name, child, size) in entries {
+        match child {
+            Some(child) => render_dir(file_system, child, depth + 1, output),
+            None => output.push_str(&format!("{}- {} (file, size={})\n", "  ".repeat(depth + 1), name, size)),
+        }
+    }
+}
+
+pub fn human_size(size: usize) -> String {
+    let units = ["", "K", "M", "G", "T", "P"];
+    let round = |value: f64| if value < 10.0 { (value * 10.0).ceil() / 10.0 } else { value.ceil() };
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while round(value) >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    let rounded = round(value);
+    match unit {
+        0 => size.to_string(),
+        _ if rounded < 10.0 => format!("{:.1}{}", rounded, units[unit]),
+        _ => format!("{}{}", rounded, units[unit]),
+    }
 }
 
-fn calculate_dir_sizes<'a>(files: &[(String, usize)], dirs: &'a [String]) -> Vec<(&'a str, usize)> {
-    dirs
-        .iter()
-        .map(|dir| {
-            (
-                dir.as_str(),
-                files
-                    .iter()
-                    .filter(|(name, _size)| name.starts_with(dir))
-                    .map(|(_name, size)| size)
-                    .sum::<usize>()
-            )
-        })
+pub fn du_report(file_system: &FileSystem) -> String {
+    (0..file_system.dirs.len())
+        .sorted_by_key(|&dir| (Reverse(file_system.dirs[dir].size), file_system.path(dir)))
+        .map(|dir| format!("{}\t{}\n", human_size(file_system.dirs[dir].size), file_system.path(dir)))
         .collect()
 }
 
+pub fn deletion_candidates(file_system: &FileSystem, disk_size: usize, required_free: usize) -> Vec<usize> {
+    let free = disk_size.saturating_sub(file_system.dirs[ROOT].size);
+    let Some(needed) = required_free.checked_sub(free).filter(|needed| *needed > 0) else {
+        return vec![];
+    };
+
+    (0..file_system.dirs.len())
+        .filter(|&dir| file_system.dirs[dir].size >= needed)
+        .sorted_by_key(|&dir| file_system.dirs[dir].size)
+        .collect()
+}
+
+pub fn deletion_report(file_system: &FileSystem, disk_size: usize, required_free: usize) -> String {
+    let free = disk_size.saturating_sub(file_system.dirs[ROOT].size);
+    let mut output = format!("{} of {} bytes used, {} free, {} required\n", file_system.dirs[ROOT].size, disk_size, free, required_free);
+
+    for dir in deletion_candidates(file_system, disk_size, required_free) {
+        output.push_str(&format!("{}\t{}\n", file_system.dirs[dir].size, file_system.path(dir)));
+    }
+
+    output
+}
+
 #[aoc(day7, part1)]
 fn part1(input: &[Line]) -> usize {
-    let (files, dirs) = parse_lines(input);
-
-    calculate_dir_sizes(&files, &dirs)
-        .into_iter()
-        .map(|(_name, size)| size)
+    build_file_system(input)
+        .dir_sizes()
         .filter(|size| *size <= 100000)
         .sum()
 }
 
-#[aoc(day7, part2)]
-fn part2(input: &[Line]) -> usize {
-    let (files, dirs) = parse_lines(input);
-    let total_used = files.iter().map(|(_name, size)| size).sum::<usize>();
-    let needed = total_used - 40000000;
+pub fn smallest_deletion(file_system: &FileSystem, disk_size: usize, required_free: usize) -> anyhow::Result<usize> {
+    deletion_candidates(file_system, disk_size, required_free)
+        .first()
+        .map(|&dir| file_system.dirs[dir].size)
+        .with_context(|| format!("No directory needs to be deleted to free {} of {} bytes", required_free, disk_size))
+}
 
-    calculate_dir_sizes(&files, &dirs)
-        .into_iter()
-        .map(|(_name, size)| size)
-        .sorted()
-        .find(|size| *size >= needed)
-        .unwrap()
+#[aoc(day7, part2)]
+fn part2(input: &[Line]) -> anyhow::Result<usize> {
+    smallest_deletion(&build_file_system(input), DISK_SIZE, REQUIRED_FREE)
 }
 
 #[cfg(test)]
@@ -127,21 +376,154 @@ mod tests {
 
     #[test]
     fn part1_example1() {
-        assert_eq!(95437, part1(&parse(include_str!("../input/2022/day7.part1.test.95437.txt"))));
+        assert_eq!(95437, part1(&parse(include_str!("../input/2022/day7.part1.test.95437.txt")).unwrap()));
     }
 
     #[test]
     fn part1_input() {
-        assert_eq!(1886043, part1(&parse(include_str!("../input/2022/day7.txt"))));
+        assert_eq!(1886043, part1(&parse(include_str!("../input/2022/day7.txt")).unwrap()));
     }
 
     #[test]
     fn part2_example1() {
-        assert_eq!(24933642, part2(&parse(include_str!("../input/2022/day7.part2.test.24933642.txt"))));
+        assert_eq!(24933642, part2(&parse(include_str!("../input/2022/day7.part2.test.24933642.txt")).unwrap()).unwrap());
     }
 
     #[test]
     fn part2_input() {
-        assert_eq!(3842121, part2(&parse(include_str!("../input/2022/day7.txt"))));
+        assert_eq!(3842121, part2(&parse(include_str!("../input/2022/day7.txt")).unwrap()).unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn du_example1() {
+        let file_system = build_file_system(&parse(include_str!("../input/2022/day7.part1.test.95437.txt")).unwrap());
+
+        assert_eq!(Some(584), file_system.du("/a/e"));
+        assert_eq!(Some(94853), file_system.du("/a"));
+        assert_eq!(Some(24933642), file_system.du("/d"));
+        assert_eq!(Some(48381165), file_system.du("/"));
+        assert_eq!(Some(94853), file_system.du("/a/e/.."));
+        assert_eq!(None, file_system.du("/x"));
+        assert_eq!("/a/e", file_system.path(file_system.resolve("a/e").unwrap()));
+    }
+
+    #[test]
+    fn repeated_ls_and_cd_root() {
+        let input = "$ cd /\n$ ls\ndir a\n10 b.txt\n$ cd a\n$ ls\n5 c.txt\n$ cd /\n$ ls\ndir a\n10 b.txt\n$ cd a\n$ ls\n5 c.txt";
+        let file_system = build_file_system(&parse(input).unwrap());
+
+        assert_eq!(2, file_system.dirs.len());
+        assert_eq!(Some(15), file_system.du("/"));
+        assert_eq!(Some(5), file_system.du("/a"));
+    }
+
+    #[test]
+    fn render_tree_example1() {
+        let expected = [
+            "- / (dir, size=48381165)",
+            "  - a (dir, size=94853)",
+            "    - e (dir, size=584)",
+            "      - i (file, size=584)",
+            "    - f (file, size=29116)",
+            "    - g (file, size=2557)",
+            "    - h.lst (file, size=62596)",
+            "  - b.txt (file, size=14848514)",
+            "  - c.dat (file, size=8504156)",
+            "  - d (dir, size=24933642)",
+            "    - d.ext (file, size=5626152)",
+            "    - d.log (file, size=8033020)",
+            "    - j (file, size=4060174)",
+            "    - k (file, size=7214296)",
+            "",
+        ].join("\n");
+
+        assert_eq!(expected, render_tree(&build_file_system(&parse(include_str!("../input/2022/day7.part1.test.95437.txt")).unwrap())));
+    }
+
+    #[test]
+    fn du_report_example1() {
+        let expected = "47M\t/\n24M\t/d\n93K\t/a\n584\t/a/e\n";
+        assert_eq!(expected, du_report(&build_file_system(&parse(include_str!("../input/2022/day7.part1.test.95437.txt")).unwrap())));
+        assert_eq!("1.5K", human_size(1536));
+        assert_eq!("1.0K", human_size(1024));
+        assert_eq!("1023", human_size(1023));
+        assert_eq!("1.0M", human_size(1048575));
+        assert_eq!("1.0M", human_size(1048576));
+        assert_eq!("10K", human_size(10189));
+    }
+
+    #[test]
+    fn deletion_report_example1() {
+        let file_system = build_file_system(&parse(include_str!("../input/2022/day7.part1.test.95437.txt")).unwrap());
+        let expected = "48381165 of 70000000 bytes used, 21618835 free, 30000000 required\n24933642\t/d\n48381165\t/\n";
+
+        assert_eq!(expected, deletion_report(&file_system, 70000000, 30000000));
+        assert_eq!(vec![ROOT], deletion_candidates(&file_system, 50000000, 30000000));
+        assert_eq!(24933642, smallest_deletion(&file_system, 70000000, 30000000).unwrap());
+        assert!(smallest_deletion(&file_system, 100000000, 30000000).is_err());
+        assert!(deletion_candidates(&file_system, 80000000, 30000000).is_empty());
+    }
+
+    #[test]
+    fn validate_input() {
+        assert_eq!(Vec::<Diagnostic>::new(), validate(&parse(include_str!("../input/2022/day7.txt")).unwrap()));
+        assert!(build_file_system_strict(&parse(include_str!("../input/2022/day7.part1.test.95437.txt")).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn validate_inconsistencies() {
+        let input = [
+            "$ cd /",
+            "$ ls",
+            "dir a",
+            "10 b.txt",
+            "$ cd b",
+            "$ cd /",
+            "$ cd ..",
+            "5 c.txt",
+            "$ ls",
+            "dir a",
+            "11 b.txt",
+        ].join("\n");
+        let lines = parse(&input).unwrap();
+        let expected = vec![
+            Diagnostic { line: 5, issue: Issue::UnlistedDirectory { path: "/b".to_string() } },
+            Diagnostic { line: 7, issue: Issue::AboveRoot },
+            Diagnostic { line: 8, issue: Issue::OutputWithoutLs },
+            Diagnostic { line: 9, issue: Issue::ConflictingListing { path: "/".to_string(), first_line: 2 } },
+        ];
+
+        assert_eq!(expected, validate(&lines));
+        assert_eq!(
+            "Inconsistent transcript:\nline 5: cd into /b, which was never listed\nline 7: cd .. above the root directory\n\
+             line 8: listing output without a preceding ls\nline 9: listing of / differs from the one on line 2",
+            build_file_system_strict(&lines).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn validate_repeated_unlisted_cd() {
+        let input = [
+            "$ cd /",
+            "$ ls",
+            "dir a",
+            "$ cd b",
+            "$ cd ..",
+            "$ cd b",
+            "$ cd ..",
+            "$ cd a",
+        ].join("\n");
+        let expected = vec![
+            Diagnostic { line: 4, issue: Issue::UnlistedDirectory { path: "/b".to_string() } },
+            Diagnostic { line: 6, issue: Issue::UnlistedDirectory { path: "/b".to_string() } },
+        ];
+
+        assert_eq!(expected, validate(&parse(&input).unwrap()));
+    }
+
+    #[test]
+    fn parse_unexpected_input() {
+        assert_eq!("Invalid transcript line 2", parse("$ cd /\n$ rm -rf").unwrap_err().to_string());
+        assert!(parse("$ cd /\nabc d.txt").is_err());
+    }
+}
@@ -1,87 +1,278 @@
-use std::cmp::{max, min};
+use anyhow::{ensure, Context, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
+use crate::ocr::to_pbm;
+
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<u8>,
+}
+
+impl Grid {
+    pub fn get(&self, row: usize, col: usize) -> u8 {
+        self.cells[row * self.width + col]
+    }
+
+    fn sight_lines(&self) -> impl Iterator<Item=Vec<usize>> + '_ {
+        let rows = (0..self.height).map(|row| (0..self.width).map(|col| row * self.width + col).collect::<Vec<_>>());
+        let cols = (0..self.width).map(|col| (0..self.height).map(|row| row * self.width + col).collect::<Vec<_>>());
+
+        rows.chain(cols).flat_map(|line| [line.iter().rev().copied().collect(), line])
+    }
+
+    pub fn visibility(&self) -> Vec<bool> {
+        let mut visible = vec![false; self.cells.len()];
+
+        for line in self.sight_lines() {
+            let mut tallest = None;
+
+            for i in line {
+                if tallest.is_none_or(|tallest| self.cells[i] > tallest) {
+                    visible[i] = true;
+                    tallest = Some(self.cells[i]);
+                }
+            }
+        }
+
+        visible
+    }
+
+    pub fn scenic_scores(&self) -> Vec<usize> {
+        let mut scores = vec![1; self.cells.len()];
+
+        for line in self.sight_lines() {
+            let mut blockers: Vec<usize> = vec![];
+
+            for (k, &i) in line.iter().enumerate() {
+                while blockers.last().is_some_and(|&b| self.cells[line[b]] < self.cells[i]) {
+                    blockers.pop();
+                }
+
+                scores[i] *= k - blockers.last().copied().unwrap_or(0);
+                blockers.push(k);
+            }
+        }
+
+        scores
+    }
+
+    fn rows<T: Copy>(&self, values: &[T], format: impl Fn(T) -> String, separator: &str) -> String {
+        values
+            .chunks(self.width)
+            .map(|row| row.iter().map(|&v| format(v)).collect::<Vec<_>>().join(separator) + "\n")
+            .collect()
+    }
+
+    pub fn render_visibility(&self) -> String {
+        self.rows(&self.visibility(), |visible| if visible { "#" } else { "." }.to_string(), "")
+    }
+
+    pub fn render_scenic_scores(&self) -> String {
+        let scores = self.scenic_scores();
+        let best = scores.iter().copied().max().unwrap_or_default().max(1);
+        let levels = SHADES.len() - 1;
+
+        self.rows(&scores, |score| (SHADES[(score * levels).div_ceil(best)] as char).to_string(), "")
+    }
+
+    pub fn report_csv(&self) -> String {
+        let visibility = self.visibility();
+        let scores = self.scenic_scores();
+        let mut output = "row,col,height,visible,scenic_score\n".to_string();
+
+        for i in 0..self.cells.len() {
+            output.push_str(&format!("{},{},{},{},{}\n", i / self.width, i % self.width, self.cells[i], visibility[i], scores[i]));
+        }
+
+        output
+    }
+
+    pub fn scenic_scores_pgm(&self) -> String {
+        let scores = self.scenic_scores();
+        let best = scores.iter().copied().max().unwrap_or_default().max(1);
+        let pixels = self.rows(&scores, |score| (score * 255 / best).to_string(), " ");
+
+        format!("P2\n{} {}\n255\n{}", self.width, self.height, pixels)
+    }
+
+    pub fn visibility_pbm(&self) -> Result<String> {
+        to_pbm('#', &self.render_visibility().replace('\n', ""), self.width)
+    }
+}
 
 #[aoc_generator(day8)]
-fn parse(input: &str) -> Vec<Vec<u32>> {
-    input
+fn parse(input: &str) -> Result<Grid> {
+    let width = input.lines().next().context("Empty input")?.len();
+    ensure!(width > 0, "Empty first row");
+    let cells = input
         .lines()
-        .map(|line| {
+        .enumerate()
+        .map(|(row, line)| {
+            ensure!(line.len() == width, "Row {} has length {}, expected {}", row + 1, line.len(), width);
+
             line
                 .chars()
-                .map(|c| c.to_digit(10).unwrap())
-                .collect()
+                .map(|c| c.to_digit(10).map(|d| d as u8).with_context(|| format!("Invalid tree height in row {}: {}", row + 1, c)))
+                .collect::<Result<Vec<_>>>()
         })
-        .collect()
+        .collect::<Result<Vec<_>>>()?
+        .concat();
+
+    Ok(Grid { width, height: cells.len() / width, cells })
 }
 
 #[aoc(day8, part1)]
-fn part1(input: &Vec<Vec<u32>>) -> usize {
-    let height = input.len();
-    let width = input[0].len();
-
-    let mut count = 0;
-
-    for row in 0..height {
-        for col in 0..width {
-            let visible =
-                (0..row).all(|i| input[i][col] < input[row][col]) ||
-                ((row+1)..height).all(|i| input[i][col] < input[row][col]) ||
-                (0..col).all(|i| input[row][i] < input[row][col]) ||
-                ((col+1)..width).all(|i| input[row][i] < input[row][col]);
-
-            if visible {
-                count += 1;
-            }
-        }
-    }
-
-    count
+fn part1(input: &Grid) -> usize {
+    input.visibility()
+        .iter()
+        .filter(|visible| **visible)
+        .count()
 }
 
 #[aoc(day8, part2)]
-fn part2(input: &Vec<Vec<u32>>) -> usize {
-    let height = input.len();
-    let width = input[0].len();
-
-    let mut best_score = 0;
-
-    for row in 0..height {
-        for col in 0..width {
-            let distances = vec![
-                min((0..row).rev().take_while(|&i| input[i][col] < input[row][col]).count() + 1, row),
-                min(((row+1)..height).take_while(|&i| input[i][col] < input[row][col]).count() + 1, height - row - 1),
-                min((0..col).rev().take_while(|&i| input[row][i] < input[row][col]).count() + 1, col),
-                min(((col+1)..width).take_while(|&i| input[row][i] < input[row][col]).count() + 1, width - col - 1),
-            ];
-            let score = distances.iter().product();
-            best_score = max(best_score, score);
-        }
-    }
-
-    best_score
+fn part2(input: &Grid) -> usize {
+    input.scenic_scores()
+        .into_iter()
+        .max()
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cmp::{max, min};
     use super::*;
 
     #[test]
     fn part1_example1() {
-        assert_eq!(21, part1(&parse(include_str!("../input/2022/day8.part1.test.21.txt"))));
+        assert_eq!(21, part1(&parse(include_str!("../input/2022/day8.part1.test.21.txt")).unwrap()));
     }
 
     #[test]
     fn part2_example1() {
-        assert_eq!(8, part2(&parse(include_str!("../input/2022/day8.part2.test.8.txt"))));
+        assert_eq!(8, part2(&parse(include_str!("../input/2022/day8.part2.test.8.txt")).unwrap()));
     }
 
     #[test]
     fn part1_input() {
-        assert_eq!(1859, part1(&parse(include_str!("../input/2022/day8.txt"))));
+        assert_eq!(1859, part1(&parse(include_str!("../input/2022/day8.txt")).unwrap()));
     }
 
     #[test]
     fn part2_input() {
-        assert_eq!(332640, part2(&parse(include_str!("../input/2022/day8.txt"))));
+        assert_eq!(332640, part2(&parse(include_str!("../input/2022/day8.txt")).unwrap()));
+    }
+
+    fn part1_naive(input: &[Vec<u32>]) -> usize {
+        let height = input.len();
+        let width = input[0].len();
+
+        let mut count = 0;
+
+        for row in 0..height {
+            for col in 0..width {
+                let visible =
+                    (0..row).all(|i| input[i][col] < input[row][col]) ||
+                    ((row+1)..height).all(|i| input[i][col] < input[row][col]) ||
+                    (0..col).all(|i| input[row][i] < input[row][col]) ||
+                    ((col+1)..width).all(|i| input[row][i] < input[row][col]);
+
+                if visible {
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
+    fn part2_naive(input: &[Vec<u32>]) -> usize {
+        let height = input.len();
+        let width = input[0].len();
+
+        let mut best_score = 0;
+
+        for row in 0..height {
+            for col in 0..width {
+                let distances = [
+                    min((0..row).rev().take_while(|&i| input[i][col] < input[row][col]).count() + 1, row),
+                    min(((row+1)..height).take_while(|&i| input[i][col] < input[row][col]).count() + 1, height - row - 1),
+                    min((0..col).rev().take_while(|&i| input[row][i] < input[row][col]).count() + 1, col),
+                    min(((col+1)..width).take_while(|&i| input[row][i] < input[row][col]).count() + 1, width - col - 1),
+                ];
+                let score = distances.iter().product();
+                best_score = max(best_score, score);
+            }
+        }
+
+        best_score
+    }
+
+    #[test]
+    fn matches_naive_on_random_forests() {
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..200 {
+            let (height, width) = (1 + next() as usize % 12, 1 + next() as usize % 12);
+            let max_height = 1 + next() % 10;
+            let rows = (0..height)
+                .map(|_| (0..width).map(|_| (next() % max_height) as u32).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let grid = Grid {
+                width,
+                height,
+                cells: rows.iter().flatten().map(|&h| h as u8).collect(),
+            };
+
+            assert_eq!(part1_naive(&rows), part1(&grid));
+            assert_eq!(part2_naive(&rows), part2(&grid));
+        }
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse("123\n45").is_err());
+        assert!(parse("123\n4x6").is_err());
+        assert_eq!("Empty first row", parse("\n").unwrap_err().to_string());
+    }
+
+    #[test]
+    fn render_visibility_example1() {
+        let expected = "#####\n###.#\n##.##\n#.#.#\n#####\n";
+        assert_eq!(expected, parse(include_str!("../input/2022/day8.part1.test.21.txt")).unwrap().render_visibility());
+    }
+
+    #[test]
+    fn render_scenic_scores_example1() {
+        let expected = "     \n :+: \n #:- \n :@= \n     \n";
+        assert_eq!(expected, parse(include_str!("../input/2022/day8.part2.test.8.txt")).unwrap().render_scenic_scores());
+    }
+
+    #[test]
+    fn report_csv_example1() {
+        let report = parse(include_str!("../input/2022/day8.part2.test.8.txt")).unwrap().report_csv();
+        let lines = report.lines().collect::<Vec<_>>();
+
+        assert_eq!(26, lines.len());
+        assert_eq!("row,col,height,visible,scenic_score", lines[0]);
+        assert_eq!("1,2,5,true,4", lines[8]);
+        assert_eq!("3,2,5,true,8", lines[18]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn export_images_example1() {
+        let grid = parse(include_str!("../input/2022/day8.part2.test.8.txt")).unwrap();
+        let pgm = grid.scenic_scores_pgm();
+
+        assert!(pgm.starts_with("P2\n5 5\n255\n0 0 0 0 0\n0 31 127 31 0\n"));
+        assert_eq!(Ok((5, grid.render_visibility().replace('\n', ""))), crate::ocr::read_netpbm(grid.visibility_pbm().unwrap().as_bytes(), 1).map_err(|e| e.to_string()));
+    }
+}
@@ -1,15 +1,20 @@
 use std::collections::HashSet;
 use std::str::FromStr;
 use aoc_runner_derive::{aoc, aoc_generator};
-use anyhow::{Result, bail};
-use crate::day09::Direction::{Down, Left, Right, Up};
+use anyhow::{Result, bail, ensure};
+use itertools::Itertools;
+use crate::day09::Direction::{Down, DownLeft, DownRight, Left, Right, Up, UpLeft, UpRight};
 
 #[derive(Debug, Copy, Clone)]
-enum Direction {
+pub enum Direction {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 impl FromStr for Direction {
@@ -21,13 +26,17 @@ impl FromStr for Direction {
             "D" => Ok(Down),
             "L" => Ok(Left),
             "R" => Ok(Right),
+            "UL" => Ok(UpLeft),
+            "UR" => Ok(UpRight),
+            "DL" => Ok(DownLeft),
+            "DR" => Ok(DownRight),
             _ => bail!("Unknown direction: {}", s),
         }
     }
 }
 
 #[aoc_generator(day9)]
-fn parse(input: &str) -> Result<Vec<(Direction, usize)>> {
+pub fn parse(input: &str) -> Result<Vec<(Direction, usize)>> {
     input
         .lines()
         .map(|line| {
@@ -52,41 +61,149 @@ fn apply_movement((x, y): (i32, i32), movement: Direction) -> (i32, i32) {
         Down => (x, y - 1),
         Left => (x - 1, y),
         Right => (x + 1, y),
+        UpLeft => (x - 1, y + 1),
+        UpRight => (x + 1, y + 1),
+        DownLeft => (x - 1, y - 1),
+        DownRight => (x + 1, y - 1),
     }
 }
 
-fn follow((hx, hy): (i32, i32), (tx, ty): (i32, i32)) -> (i32, i32) {
-    if (hx - tx).abs() <= 1 && (hy - ty).abs() <= 1 {
-        return (tx, ty);
+fn step_towards((hx, hy): (i32, i32), (tx, ty): (i32, i32)) -> (i32, i32) {
+    (tx + (hx - tx).signum(), ty + (hy - ty).signum())
+}
+
+pub trait FollowRule {
+    fn follow(&self, head: (i32, i32), tail: (i32, i32)) -> (i32, i32);
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Touching;
+
+impl FollowRule for Touching {
+    fn follow(&self, (hx, hy): (i32, i32), (tx, ty): (i32, i32)) -> (i32, i32) {
+        if (hx - tx).abs() <= 1 && (hy - ty).abs() <= 1 {
+            return (tx, ty);
+        }
+
+        step_towards((hx, hy), (tx, ty))
     }
+}
 
-    (tx + (hx - tx).signum(), ty + (hy - ty).signum())
+#[derive(Debug, Copy, Clone)]
+pub struct Elastic(pub i32);
+
+impl FollowRule for Elastic {
+    fn follow(&self, (hx, hy): (i32, i32), (tx, ty): (i32, i32)) -> (i32, i32) {
+        if (hx - tx).abs().max((hy - ty).abs()) <= self.0 {
+            return (tx, ty);
+        }
+
+        step_towards((hx, hy), (tx, ty))
+    }
 }
 
-fn solve(input: &[(Direction, usize)], num_knots: usize) -> usize {
-    let mut knots = vec![(0, 0); num_knots];
-    let mut tail_been = HashSet::from([(0, 0)]);
+#[derive(Debug, Copy, Clone)]
+pub struct Manhattan(pub i32);
+
+impl FollowRule for Manhattan {
+    fn follow(&self, (hx, hy): (i32, i32), (tx, ty): (i32, i32)) -> (i32, i32) {
+        if (hx - tx).abs() + (hy - ty).abs() <= self.0 {
+            return (tx, ty);
+        }
+
+        step_towards((hx, hy), (tx, ty))
+    }
+}
+
+pub struct Simulation<F> {
+    movements: std::vec::IntoIter<Direction>,
+    knots: Vec<(i32, i32)>,
+    rule: F,
+}
 
-    for movement in expand_movements(input) {
-        knots[0] = apply_movement(knots[0], movement);
+impl<F: FollowRule> Iterator for Simulation<F> {
+    type Item = Vec<(i32, i32)>;
 
-        for i in 1..(knots.len()) {
-            knots[i] = follow(knots[i - 1], knots[i]);
+    fn next(&mut self) -> Option<Self::Item> {
+        let movement = self.movements.next()?;
+        self.knots[0] = apply_movement(self.knots[0], movement);
+
+        for i in 1..(self.knots.len()) {
+            self.knots[i] = self.rule.follow(self.knots[i - 1], self.knots[i]);
         }
 
-        tail_been.insert(knots[knots.len() - 1]);
+        Some(self.knots.clone())
     }
+}
+
+pub fn simulate<F: FollowRule>(input: &[(Direction, usize)], num_knots: usize, rule: F) -> Result<Simulation<F>> {
+    ensure!(num_knots > 0, "A rope needs at least one knot");
+
+    Ok(Simulation {
+        movements: expand_movements(input).into_iter(),
+        knots: vec![(0, 0); num_knots],
+        rule,
+    })
+}
+
+pub fn visit_counts(input: &[(Direction, usize)], num_knots: usize, rule: impl FollowRule) -> Result<Vec<usize>> {
+    Ok(simulate(input, num_knots, rule)?
+        .fold(vec![HashSet::from([(0, 0)]); num_knots], |mut visited, knots| {
+            for (visited, knot) in visited.iter_mut().zip(knots) {
+                visited.insert(knot);
+            }
 
-    tail_been.len()
+            visited
+        })
+        .iter()
+        .map(HashSet::len)
+        .collect())
+}
+
+fn knot_label(i: usize, num_knots: usize) -> char {
+    match i {
+        0 => 'H',
+        1 if num_knots == 2 => 'T',
+        _ => char::from_digit(i as u32 % 36, 36).unwrap_or('?'),
+    }
+}
+
+pub fn render(knots: &[(i32, i32)], trail: &HashSet<(i32, i32)>) -> String {
+    let positions = knots.iter().chain(trail).chain([(0, 0)].iter()).collect::<Vec<_>>();
+    let (min_x, max_x) = positions.iter().map(|(x, _)| *x).minmax().into_option().unwrap_or_default();
+    let (min_y, max_y) = positions.iter().map(|(_, y)| *y).minmax().into_option().unwrap_or_default();
+
+    (min_y..=max_y)
+        .rev()
+        .map(|y| {
+            (min_x..=max_x)
+                .map(|x| {
+                    if let Some(i) = knots.iter().position(|&knot| knot == (x, y)) {
+                        knot_label(i, knots.len())
+                    } else if (x, y) == (0, 0) {
+                        's'
+                    } else if trail.contains(&(x, y)) {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>() + "\n"
+        })
+        .collect()
+}
+
+fn solve(input: &[(Direction, usize)], num_knots: usize) -> Result<usize> {
+    Ok(visit_counts(input, num_knots, Touching)?[num_knots - 1])
 }
 
 #[aoc(day9, part1)]
-fn part1(input: &[(Direction, usize)]) -> usize {
+fn part1(input: &[(Direction, usize)]) -> Result<usize> {
     solve(input, 2)
 }
 
 #[aoc(day9, part2)]
-fn part2(input: &[(Direction, usize)]) -> usize {
+fn part2(input: &[(Direction, usize)]) -> Result<usize> {
     solve(input, 10)
 }
 
@@ -96,26 +213,82 @@ mod tests {
 
     #[test]
     fn part1_example1() {
-        assert_eq!(13, part1(&parse(include_str!("../input/2022/day9.part1.test.13.txt")).unwrap()));
+        assert_eq!(13, part1(&parse(include_str!("../input/2022/day9.part1.test.13.txt")).unwrap()).unwrap());
     }
 
     #[test]
     fn part1_input() {
-        assert_eq!(6090, part1(&parse(include_str!("../input/2022/day9.txt")).unwrap()));
+        assert_eq!(6090, part1(&parse(include_str!("../input/2022/day9.txt")).unwrap()).unwrap());
     }
 
     #[test]
     fn part2_example1() {
-        assert_eq!(1, part2(&parse(include_str!("../input/2022/day9.part2.test.1.txt")).unwrap()));
+        assert_eq!(1, part2(&parse(include_str!("../input/2022/day9.part2.test.1.txt")).unwrap()).unwrap());
     }
 
     #[test]
     fn part2_example2() {
-        assert_eq!(36, part2(&parse(include_str!("../input/2022/day9.part2.test.36.txt")).unwrap()));
+        assert_eq!(36, part2(&parse(include_str!("../input/2022/day9.part2.test.36.txt")).unwrap()).unwrap());
     }
 
     #[test]
     fn part2_input() {
-        assert_eq!(2566, part2(&parse(include_str!("../input/2022/day9.txt")).unwrap()));
+        assert_eq!(2566, part2(&parse(include_str!("../input/2022/day9.txt")).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn visit_counts_example2() {
+        assert_eq!(vec![96, 88, 80, 72, 64, 56, 50, 46, 41, 36], visit_counts(&parse(include_str!("../input/2022/day9.part2.test.36.txt")).unwrap(), 10, Touching).unwrap());
+    }
+
+    #[test]
+    fn simulate_and_render_example1() {
+        let input = parse(include_str!("../input/2022/day9.part1.test.13.txt")).unwrap();
+        let mut trail = HashSet::from([(0, 0)]);
+        let states = simulate(&input, 2, Touching).unwrap()
+            .inspect(|knots| {
+                trail.insert(knots[1]);
+            })
+            .take(4)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![(4, 0), (3, 0)], states[3]);
+        assert_eq!("s##TH\n", render(&states[3], &trail));
+        assert_eq!(24, simulate(&input, 2, Touching).unwrap().count());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn render_example2() {
+        let input = parse(include_str!("../input/2022/day9.part2.test.36.txt")).unwrap();
+        let knots = simulate(&input, 10, Touching).unwrap().take(5).last().unwrap();
+        assert_eq!("54321H\n", render(&knots, &HashSet::new()));
+    }
+
+    #[test]
+    fn diagonal_moves() {
+        let input = parse("UR 3\nDL 1\nR 1").unwrap();
+        let states = simulate(&input, 3, Touching).unwrap().collect::<Vec<_>>();
+
+        assert_eq!(vec![(3, 3), (2, 2), (1, 1)], states[2]);
+        assert_eq!(vec![(3, 2), (2, 2), (1, 1)], states[4]);
+        assert!(parse("UU 1").is_err());
+    }
+
+    #[test]
+    fn follow_rules() {
+        let input = parse(include_str!("../input/2022/day9.txt")).unwrap();
+        assert_eq!(visit_counts(&input, 10, Touching).unwrap(), visit_counts(&input, 10, Elastic(1)).unwrap());
+
+        let input = parse("R 4\nU 2").unwrap();
+        assert_eq!(vec![(4, 2), (2, 0)], simulate(&input, 2, Elastic(2)).unwrap().last().unwrap());
+        assert_eq!(vec![(4, 2), (3, 1)], simulate(&input, 2, Manhattan(2)).unwrap().last().unwrap());
+        assert_eq!(vec![7, 3, 1], visit_counts(&input, 3, Elastic(2)).unwrap());
+    }
+
+    #[test]
+    fn no_knots() {
+        let input = parse("R 4\nU 2").unwrap();
+        assert!(simulate(&input, 0, Touching).is_err());
+        assert!(solve(&input, 0).is_err());
+    }
+}
@@ -1,85 +1,383 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
 use std::str::FromStr;
 use aoc_runner_derive::{aoc, aoc_generator};
-use anyhow::{Result, bail, Context};
-use crate::day10::Instruction::{AddX, NoOp};
-use crate::ocr::ocr;
+use anyhow::{Result, bail, ensure};
+use crate::ocr::{CHAR_HEIGHT, ocr, render_text, to_pbm};
 
-#[derive(Debug, Copy, Clone)]
-enum Instruction {
-    NoOp,
-    AddX(i32),
+#[derive(Copy, Clone)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    pub cycles: usize,
+    pub arity: usize,
+    pub execute: fn(i32, &[i32]) -> i32,
+}
+
+impl fmt::Debug for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.mnemonic, self.cycles)
+    }
+}
+
+impl PartialEq for Opcode {
+    fn eq(&self, other: &Opcode) -> bool {
+        self.mnemonic == other.mnemonic && self.cycles == other.cycles && self.arity == other.arity
+    }
+}
+
+pub const NOOP: Opcode = Opcode { mnemonic: "noop", cycles: 1, arity: 0, execute: |x, _| x };
+// Like a real 32-bit register, X wraps around on overflow; custom opcodes should do the same
+pub const ADDX: Opcode = Opcode { mnemonic: "addx", cycles: 2, arity: 1, execute: |x, operands| x.wrapping_add(operands[0]) };
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub operands: Vec<i32>,
+}
+
+impl Instruction {
+    pub fn noop() -> Instruction {
+        Instruction { opcode: NOOP, operands: vec![] }
+    }
+
+    pub fn addx(v: i32) -> Instruction {
+        Instruction { opcode: ADDX, operands: vec![v] }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstructionSet {
+    opcodes: Vec<Opcode>,
+}
+
+impl Default for InstructionSet {
+    fn default() -> InstructionSet {
+        InstructionSet { opcodes: vec![NOOP, ADDX] }
+    }
+}
+
+impl InstructionSet {
+    pub fn with(mut self, opcode: Opcode) -> InstructionSet {
+        self.opcodes.retain(|existing| existing.mnemonic != opcode.mnemonic);
+        self.opcodes.push(opcode);
+        self
+    }
+
+    pub fn parse_instruction(&self, s: &str) -> Result<Instruction> {
+        let mut parts = s.split_ascii_whitespace();
+        let Some(opcode) = parts.next().and_then(|mnemonic| self.opcodes.iter().find(|opcode| opcode.mnemonic == mnemonic)) else {
+            bail!("Malformed input line: {}", s);
+        };
+        let operands = parts.map(str::parse).collect::<Result<Vec<i32>, _>>()?;
+        ensure!(operands.len() == opcode.arity, "Expected {} operand(s): {}", opcode.arity, s);
+
+        Ok(Instruction { opcode: *opcode, operands })
+    }
+
+    pub fn parse_program(&self, input: &str) -> Result<Vec<Instruction>> {
+        input
+            .lines()
+            .map(|line| self.parse_instruction(line))
+            .collect()
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.opcode.mnemonic)?;
+        for operand in &self.operands {
+            write!(f, " {}", operand)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl FromStr for Instruction {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Instruction> {
-        let mut parts = s.split(' ');
-        match parts.next() {
-            Some("noop") => Ok(NoOp),
-            Some("addx") => Ok(AddX(parts.next().context("Missing operand")?.parse()?)),
-            Some(_) | None => bail!("Malformed input line: {}", s),
-        }
+        InstructionSet::default().parse_instruction(s)
     }
 }
 
 #[aoc_generator(day10)]
-fn parse(input: &str) -> Result<Vec<Instruction>> {
+pub fn parse(input: &str) -> Result<Vec<Instruction>> {
     input
         .lines()
         .map(&str::parse)
         .collect()
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Tick {
+    pub cycle: usize,
+    pub x: i32,
+    pub pc: usize,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum Breakpoint {
+    Cycle(usize),
+    Register(fn(i32) -> bool),
+}
+
+impl Breakpoint {
+    fn hit(&self, tick: &Tick) -> bool {
+        match self {
+            Breakpoint::Cycle(cycle) => tick.cycle == *cycle,
+            Breakpoint::Register(condition) => condition(tick.x),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cpu<'a> {
+    program: &'a [Instruction],
+    pc: usize,
+    elapsed: usize,
+    cycle: usize,
+    x: i32,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl<'a> Cpu<'a> {
+    pub fn new(program: &'a [Instruction]) -> Cpu<'a> {
+        Cpu { program, pc: 0, elapsed: 0, cycle: 0, x: 1, breakpoints: vec![] }
+    }
+
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    pub fn cycle_count(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn run(&mut self) -> Option<Tick> {
+        while let Some(tick) = self.next() {
+            if self.breakpoints.iter().any(|breakpoint| breakpoint.hit(&tick)) {
+                return Some(tick);
+            }
+        }
+
+        None
+    }
+}
+
+impl Iterator for Cpu<'_> {
+    type Item = Tick;
+
+    fn next(&mut self) -> Option<Tick> {
+        let instruction = self.program.get(self.pc)?;
+        self.cycle += 1;
+        self.elapsed += 1;
+        let tick = Tick { cycle: self.cycle, x: self.x, pc: self.pc };
+
+        if self.elapsed >= instruction.opcode.cycles {
+            self.x = (instruction.opcode.execute)(self.x, &instruction.operands);
+            self.elapsed = 0;
+            self.pc += 1;
+        }
+
+        Some(tick)
+    }
+}
+
 #[aoc(day10, part1)]
 fn part1(input: &[Instruction]) -> i32 {
-    input.iter()
-        .flat_map(|instruction| match *instruction {
-            NoOp => vec![NoOp],
-            AddX(v) => vec![NoOp, AddX(v)],
-        })
-        .take(221)
-        .enumerate()
-        .map(|(cycle, instruction)| (cycle + 1, instruction))
-        .fold((1, 0), |(mut x, mut cumulative_strength), (cycle, instruction)| {
-            cumulative_strength += match cycle % 40 {
-                20 => (cycle as i32) * x,
-                _ => 0,
-            };
-
-            x += match instruction {
-                NoOp => 0,
-                AddX(v) => v,
-            };
-
-            (x, cumulative_strength)
-        })
-        .1
+    let mut cpu = Cpu::new(input);
+    for cycle in (20..=220).step_by(40) {
+        cpu.add_breakpoint(Breakpoint::Cycle(cycle));
+    }
+
+    std::iter::from_fn(|| cpu.run())
+        .map(|tick| tick.cycle as i32 * tick.x)
+        .sum()
 }
 
-fn execute_instructions(input: &[Instruction]) -> String {
-    input.iter()
-        .flat_map(|instruction| match *instruction {
-            NoOp => vec![NoOp],
-            AddX(v) => vec![NoOp, AddX(v)],
-        })
-        .enumerate()
-        .map(|(cycle, instruction)| (cycle, instruction))
-        .fold((1, vec!['.'; 240]), |(x, mut screen), (cycle, instruction)| {
-            if ((x - 1)..=(x + 1)).contains(&((cycle % 40) as i32)) {
-                screen[cycle] = '#';
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Crt {
+    width: usize,
+    height: usize,
+    sprite_width: usize,
+}
+
+impl Default for Crt {
+    fn default() -> Crt {
+        Crt { width: 40, height: 6, sprite_width: 3 }
+    }
+}
+
+impl Crt {
+    pub fn new(width: usize, height: usize, sprite_width: usize) -> Result<Crt> {
+        ensure!(width > 0 && height > 0, "Invalid screen size {}x{}", width, height);
+        ensure!(sprite_width > 0, "Sprite width must be positive");
+
+        Ok(Crt { width, height, sprite_width })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn sprite_width(&self) -> usize {
+        self.sprite_width
+    }
+
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_lit(&self, tick: &Tick) -> bool {
+        let column = ((tick.cycle - 1) % self.width) as i64;
+        let sprite_start = tick.x as i64 - (self.sprite_width as i64 - 1) / 2;
+
+        (sprite_start..(sprite_start + self.sprite_width as i64)).contains(&column)
+    }
+
+    pub fn frames<'a>(&self, program: &'a [Instruction]) -> impl Iterator<Item=String> + 'a {
+        let crt = *self;
+        let mut screen = vec!['.'; self.len()];
+
+        Cpu::new(program)
+            .take(self.len())
+            .map(move |tick| {
+                if crt.is_lit(&tick) {
+                    screen[tick.cycle - 1] = '#';
+                }
+
+                screen.iter().collect()
+            })
+    }
+
+    pub fn draw(&self, program: &[Instruction]) -> String {
+        self.frames(program)
+            .last()
+            .unwrap_or_else(|| ".".repeat(self.len()))
+    }
+
+    pub fn render(&self, screen: &str) -> String {
+        screen
+            .chars()
+            .collect::<Vec<_>>()
+            .chunks(self.width)
+            .map(|line| line.iter().collect::<String>() + "\n")
+            .collect()
+    }
+
+    pub fn animate(&self, program: &[Instruction], out: &mut impl Write, delay: Duration) -> io::Result<()> {
+        for frame in self.frames(program) {
+            write!(out, "\x1b[H\x1b[2J{}", self.render(&frame))?;
+            out.flush()?;
+            thread::sleep(delay);
+        }
+
+        Ok(())
+    }
+
+    pub fn assemble(&self, screen: &str) -> Result<Vec<Instruction>> {
+        let target = screen.chars().map(|c| c == '#').collect::<Vec<_>>();
+        ensure!(target.len() == self.len(), "Expected {} pixels, got {}", self.len(), target.len());
+
+        let candidates = -(self.sprite_width as i32)..=(self.width + self.sprite_width) as i32;
+        let matches = |position: usize, x: i32| self.is_lit(&Tick { cycle: position + 1, x, pc: 0 }) == target[position];
+        let mut previous: Vec<HashMap<i32, (i32, Instruction)>> = vec![HashMap::new(); target.len() + 1];
+        let mut reachable = vec![HashSet::new(); target.len() + 1];
+        reachable[0].insert(1);
+
+        for position in 0..target.len() {
+            for x in reachable[position].clone() {
+                if !matches(position, x) {
+                    continue;
+                }
+
+                if reachable[position + 1].insert(x) {
+                    previous[position + 1].insert(x, (x, Instruction::noop()));
+                }
+
+                if position + 2 <= target.len() && matches(position + 1, x) {
+                    for next_x in candidates.clone().filter(|&next_x| next_x != x) {
+                        if reachable[position + 2].insert(next_x) {
+                            previous[position + 2].insert(next_x, (x, Instruction::addx(next_x - x)));
+                        }
+                    }
+                }
             }
+        }
 
-            let x = match instruction {
-                NoOp => x,
-                AddX(v) => x + v,
-            };
+        let Some(&(mut x)) = reachable[target.len()].iter().min() else {
+            bail!("Target image cannot be drawn");
+        };
+        let mut position = target.len();
+        let mut program = vec![];
 
-            (x, screen)
-        })
-        .1
+        while position > 0 {
+            let (previous_x, instruction) = previous[position][&x].clone();
+            position -= instruction.opcode.cycles;
+            x = previous_x;
+            program.push(instruction);
+        }
+
+        program.reverse();
+        Ok(program)
+    }
+
+    pub fn assemble_text(&self, text: &str) -> Result<Vec<Instruction>> {
+        // The sprite starts over the first pixel, so only an empty program leaves the screen blank
+        if text.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let rendered = render_text('#', text)?.chars().collect::<Vec<_>>();
+        let text_width = rendered.len() / CHAR_HEIGHT;
+        ensure!(self.height == CHAR_HEIGHT && text_width <= self.width, "Text does not fit on a {}x{} screen", self.width, self.height);
+
+        let screen = (0..CHAR_HEIGHT)
+            .map(|row| rendered[(row * text_width)..((row + 1) * text_width)].iter().collect::<String>())
+            .map(|line| format!("{:.<width$}", line, width = self.width))
+            .collect::<String>();
+
+        self.assemble(&screen)
+    }
+}
+
+pub fn disassemble(program: &[Instruction]) -> String {
+    program
         .iter()
-        .collect::<String>()
+        .scan(1, |cycle, instruction| {
+            let start = *cycle;
+            *cycle += instruction.opcode.cycles;
+            Some(format!("{:>5}  {}\n", start, instruction))
+        })
+        .collect()
+}
+
+fn execute_instructions(input: &[Instruction]) -> String {
+    Crt::default().draw(input)
+}
+
+pub fn screen_to_pbm(input: &[Instruction]) -> Result<String> {
+    to_pbm('#', &execute_instructions(input), 40)
 }
 
 #[aoc(day10, part2)]
@@ -116,8 +414,129 @@ mod tests {
         assert_eq!(expected, execute_instructions(&parse(include_str!("../input/2022/day10.part2.test.124.txt")).unwrap()));
     }
 
+    #[test]
+    fn screen_to_pbm_input() {
+        let pbm = screen_to_pbm(&parse(include_str!("../input/2022/day10.txt")).unwrap()).unwrap();
+        assert_eq!("RUAKHBEK", crate::ocr::ocr_netpbm(pbm.as_bytes(), 1).unwrap());
+    }
+
     #[test]
     fn part2_input() {
         assert_eq!("RUAKHBEK", part2(&parse(include_str!("../input/2022/day10.txt")).unwrap()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn trace_example() {
+        let program = parse("noop\naddx 3\naddx -5").unwrap();
+        let trace = Cpu::new(&program).map(|tick| (tick.cycle, tick.x)).collect::<Vec<_>>();
+
+        assert_eq!(vec![(1, 1), (2, 1), (3, 1), (4, 4), (5, 4)], trace);
+    }
+
+    #[test]
+    fn register_wraps_around() {
+        let program = parse("addx 2147483647\nnoop").unwrap();
+        assert_eq!(Some(i32::MIN), Cpu::new(&program).last().map(|tick| tick.x));
+    }
+
+    #[test]
+    fn breakpoints() {
+        let program = parse(include_str!("../input/2022/day10.part1.test.13140.txt")).unwrap();
+        let mut cpu = Cpu::new(&program);
+        cpu.add_breakpoint(Breakpoint::Register(|x| x >= 20));
+
+        assert_eq!(Some(Tick { cycle: 19, x: 21, pc: 9 }), cpu.run());
+        assert_eq!(19, cpu.cycle_count());
+        assert!(!cpu.is_halted());
+
+        let mut cpu = Cpu::new(&program);
+        cpu.add_breakpoint(Breakpoint::Cycle(220));
+        assert_eq!(18, cpu.run().unwrap().x);
+        assert_eq!(None, cpu.run());
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn custom_instruction() {
+        let instruction_set = InstructionSet::default()
+            .with(Opcode { mnemonic: "mulx", cycles: 3, arity: 1, execute: |x, operands| x.wrapping_mul(operands[0]) });
+        let program = instruction_set.parse_program("addx 2\nmulx 4\nnoop").unwrap();
+        let trace = Cpu::new(&program).map(|tick| tick.x).collect::<Vec<_>>();
+
+        assert_eq!(vec![1, 1, 3, 3, 3, 12], trace);
+        assert!(parse("mulx 4").is_err());
+        assert!(instruction_set.parse_program("mulx").is_err());
+    }
+
+    #[test]
+    fn crt_geometry() {
+        let program = parse(include_str!("../input/2022/day10.part1.test.13140.txt")).unwrap();
+        let crt = Crt::new(20, 2, 1).unwrap();
+        let expected = [
+            ".#...#..#...#....#..",
+            "....................",
+        ].join("\n") + "\n";
+
+        assert_eq!(expected, crt.render(&crt.draw(&program)));
+        assert_eq!(execute_instructions(&program), Crt::default().draw(&program));
+        assert!(Crt::new(0, 6, 3).is_err());
+        assert!(Crt::new(40, 0, 3).is_err());
+        assert!(Crt::new(40, 6, 0).is_err());
+    }
+
+    #[test]
+    fn crt_animation() {
+        let program = parse("noop\naddx 3\naddx -5").unwrap();
+        let crt = Crt::new(5, 1, 3).unwrap();
+        let mut out = vec![];
+        crt.animate(&program, &mut out, Duration::ZERO).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(5, out.matches("\x1b[H").count());
+        assert!(out.ends_with("\x1b[H\x1b[2J#####\n"));
+    }
+
+    #[test]
+    fn assemble_screen() {
+        let program = parse(include_str!("../input/2022/day10.part1.test.13140.txt")).unwrap();
+        let screen = execute_instructions(&program);
+        let assembled = Crt::default().assemble(&screen).unwrap();
+
+        assert_eq!(screen, execute_instructions(&assembled));
+        assert_eq!(assembled, InstructionSet::default().parse_program(&disassemble(&assembled).lines().map(|line| &line[7..]).collect::<Vec<_>>().join("\n")).unwrap());
+    }
+
+    #[test]
+    fn assemble_text() {
+        let crt = Crt::default();
+
+        assert_eq!("RUAKHBEK", part2(&crt.assemble_text("RUAKHBEK").unwrap()));
+        let screen = crt.draw(&crt.assemble_text("BELL").unwrap()).chars().collect::<Vec<_>>();
+        let text = screen.chunks(40).flat_map(|line| &line[..20]).collect::<String>();
+        assert_eq!("BELL", ocr('#', &text).unwrap());
+        assert!(crt.assemble_text("HELLO").is_err());
+        assert!(crt.assemble_text("ABCDEFGHI").is_err());
+        assert_eq!(".".repeat(240), crt.draw(&crt.assemble_text("").unwrap()));
+    }
+
+    #[test]
+    fn assemble_impossible() {
+        let crt = Crt::new(3, 1, 3).unwrap();
+
+        assert!(crt.assemble("#.#").is_err());
+        assert!(crt.assemble("###").is_ok());
+    }
+
+    #[test]
+    fn disassemble_example() {
+        let program = parse("noop\naddx 3\naddx -5\nnoop").unwrap();
+        let expected = [
+            "    1  noop",
+            "    2  addx 3",
+            "    4  addx -5",
+            "    6  noop",
+        ].join("\n") + "\n";
+
+        assert_eq!(expected, disassemble(&program));
+    }
+}
@@ -1,39 +1,178 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 use aoc_runner_derive::{aoc, aoc_generator};
-use anyhow::{bail, Context, Error, Result};
+use anyhow::{bail, ensure, Context, Error, Result};
 use itertools::Itertools;
+use num_bigint::BigUint;
 use scan_fmt::scan_fmt;
-use crate::day11::Operation::{Add, Mul, Square};
+use crate::day11::Operator::{Add, Div, Mul, Sub};
 
 #[derive(Debug, Clone)]
-struct Monkey {
+pub struct Monkey {
     items: Vec<u64>,
-    operation: Operation,
+    operation: Expression,
     divisibility_test: u64,
     target_if_true: usize,
     target_if_false: usize,
-    num_inspected: usize,
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Operation {
-    Square,
-    Add(u64),
-    Mul(u64),
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
 }
 
-impl FromStr for Operation {
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Add => "+",
+            Sub => "-",
+            Mul => "*",
+            Div => "/",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Expression {
+    Old,
+    Literal(u64),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Token {
+    Old,
+    Literal(u64),
+    Operator(Operator),
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let token = match c {
+            ' ' => continue,
+            '+' => Token::Operator(Add),
+            '-' => Token::Operator(Sub),
+            '*' => Token::Operator(Mul),
+            '/' => Token::Operator(Div),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '0'..='9' => {
+                let mut end = i + 1;
+                while let Some((j, _)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    end = j + 1;
+                }
+                Token::Literal(s[i..end].parse().with_context(|| format!("Literal out of range: {}", &s[i..end]))?)
+            },
+            'o' if s[i..].starts_with("old") => {
+                chars.nth(1);
+                Token::Old
+            },
+            _ => bail!("Unexpected character '{}' in operation: {}", c, s),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn binary(&mut self, operators: &[Operator], operand: fn(&mut Parser) -> Result<Expression>) -> Result<Expression> {
+        let mut lhs = operand(self)?;
+
+        while let Some(Token::Operator(operator)) = self.peek().filter(|token| matches!(token, Token::Operator(o) if operators.contains(o))) {
+            self.position += 1;
+            lhs = Expression::Binary(operator, Box::new(lhs), Box::new(operand(self)?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn expression(&mut self) -> Result<Expression> {
+        self.binary(&[Add, Sub], Parser::term)
+    }
+
+    fn term(&mut self) -> Result<Expression> {
+        self.binary(&[Mul, Div], Parser::factor)
+    }
+
+    fn factor(&mut self) -> Result<Expression> {
+        match self.next() {
+            Some(Token::Old) => Ok(Expression::Old),
+            Some(Token::Literal(v)) => Ok(Expression::Literal(v)),
+            Some(Token::Open) => {
+                let expression = self.expression()?;
+                ensure!(self.next() == Some(Token::Close), "Missing closing parenthesis");
+                Ok(expression)
+            },
+            Some(token) => bail!("Unexpected token: {:?}", token),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+}
+
+impl Expression {
+    fn evaluate<W: Worry>(&self, old: &W) -> Result<W> {
+        match self {
+            Expression::Old => Ok(old.clone()),
+            Expression::Literal(v) => Ok(old.with_value(*v)),
+            Expression::Binary(operator, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(old)?, rhs.evaluate(old)?);
+
+                lhs.apply(*operator, &rhs).with_context(|| match operator {
+                    Div if rhs.to_biguint() == BigUint::ZERO => format!("Division by zero evaluating {} / {}", lhs, rhs),
+                    Sub => format!("Worry level underflow evaluating {} - {}", lhs, rhs),
+                    _ => format!("Worry level overflow evaluating {} {} {}", lhs, operator, rhs),
+                })
+            },
+        }
+    }
+
+    fn uses(&self, operator: Operator) -> bool {
+        match self {
+            Expression::Binary(o, lhs, rhs) => *o == operator || lhs.uses(operator) || rhs.uses(operator),
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Expression {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let parts = s.split_ascii_whitespace().collect::<Vec<_>>();
+        let Some(rhs) = s.trim().strip_prefix("new =") else {
+            bail!("Unrecognized operation: {}", s);
+        };
+        let mut parser = Parser { tokens: tokenize(rhs)?, position: 0 };
+        let expression = parser.expression().with_context(|| format!("Unrecognized operation: {}", s))?;
+        ensure!(parser.peek().is_none(), "Trailing input in operation: {}", s);
 
-        match parts[..] {
-            [ "new", "=", "old", "*", "old"] => Ok(Square),
-            [ "new", "=", "old", "+", v] => Ok(Add(v.parse()?)),
-            [ "new", "=", "old", "*", v] => Ok(Mul(v.parse()?)),
-            _ => bail!("Unrecognized operation: {}", s),
-        }
+        Ok(expression)
     }
 }
 
@@ -47,6 +186,7 @@ fn parse_monkey(input: &str) -> Result<Monkey> {
         .collect::<Result<Vec<u64>>>()?;
     let operation = scan_fmt!(input.next().context("Missing operation")?.trim(), "Operation: {/.*/}", String).map(|s| s.parse())??;
     let divisibility_test = scan_fmt!(input.next().context("Missing divisibility test")?.trim(), "Test: divisible by {d}", u64)?;
+    ensure!(divisibility_test > 0, "Divisibility test by zero");
     let target_if_true = scan_fmt!(input.next().context("Missing target if true")?.trim(), "If true: throw to monkey {d}", usize)?;
     let target_if_false = scan_fmt!(input.next().context("Missing target if false")?.trim(), "If false: throw to monkey {d}", usize)?;
 
@@ -56,61 +196,368 @@ fn parse_monkey(input: &str) -> Result<Monkey> {
         divisibility_test,
         target_if_true,
         target_if_false,
-        num_inspected: 0,
     })
 }
 
 #[aoc_generator(day11)]
-fn parse(input: &str) -> Result<Vec<Monkey>> {
+pub fn parse(input: &str) -> Result<Vec<Monkey>> {
     input
         .split("\n\n")
         .map(parse_monkey)
         .collect()
 }
 
-fn solve(monkeys: &[Monkey], rounds: usize, management_technique: impl Fn(u64) -> u64) -> usize {
-    let mut monkeys = monkeys.to_vec();
+trait Worry: Clone + fmt::Display {
+    fn with_value(&self, v: u64) -> Self;
+    fn apply(&self, operator: Operator, rhs: &Self) -> Option<Self>;
+    fn is_divisible_by(&self, divisor: u64) -> bool;
+    fn to_biguint(&self) -> BigUint;
 
-    for _ in 0..rounds {
-        for i in 0..monkeys.len() {
-            for mut item in monkeys[i].items.clone() {
-                item = match monkeys[i].operation {
-                    Square => item * item,
-                    Add(v) => item + v,
-                    Mul(v) => item * v,
-                };
-                item = management_technique(item);
-                let target = if item % monkeys[i].divisibility_test == 0 {
-                    monkeys[i].target_if_true
-                } else {
-                    monkeys[i].target_if_false
-                } as usize;
-                monkeys[target].items.push(item);
-                monkeys[i].num_inspected += 1;
-            }
+    fn modulus(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl Worry for u64 {
+    fn with_value(&self, v: u64) -> u64 {
+        v
+    }
+
+    fn apply(&self, operator: Operator, rhs: &u64) -> Option<u64> {
+        match operator {
+            Add => self.checked_add(*rhs),
+            Sub => self.checked_sub(*rhs),
+            Mul => self.checked_mul(*rhs),
+            Div => self.checked_div(*rhs),
+        }
+    }
+
+    fn is_divisible_by(&self, divisor: u64) -> bool {
+        self.is_multiple_of(divisor)
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        BigUint::from(*self)
+    }
+}
+
+impl Worry for u128 {
+    fn with_value(&self, v: u64) -> u128 {
+        v as u128
+    }
+
+    fn apply(&self, operator: Operator, rhs: &u128) -> Option<u128> {
+        match operator {
+            Add => self.checked_add(*rhs),
+            Sub => self.checked_sub(*rhs),
+            Mul => self.checked_mul(*rhs),
+            Div => self.checked_div(*rhs),
+        }
+    }
+
+    fn is_divisible_by(&self, divisor: u64) -> bool {
+        self.is_multiple_of(divisor as u128)
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        BigUint::from(*self)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Modular {
+    value: u64,
+    modulus: u64,
+}
+
+impl fmt::Display for Modular {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (mod {})", self.value, self.modulus)
+    }
+}
+
+impl Worry for Modular {
+    fn with_value(&self, v: u64) -> Modular {
+        Modular { value: v % self.modulus, modulus: self.modulus }
+    }
+
+    fn apply(&self, operator: Operator, rhs: &Modular) -> Option<Modular> {
+        let (lhs, rhs, modulus) = (self.value as u128, rhs.value as u128, self.modulus as u128);
+        let value = match operator {
+            Add => (lhs + rhs) % modulus,
+            Mul => (lhs * rhs) % modulus,
+            Sub | Div => return None,
+        };
+
+        Some(Modular { value: value as u64, modulus: self.modulus })
+    }
+
+    fn is_divisible_by(&self, divisor: u64) -> bool {
+        self.value.is_multiple_of(divisor)
+    }
 
-            monkeys[i].items = vec![];
+    fn to_biguint(&self) -> BigUint {
+        BigUint::from(self.value)
+    }
+
+    fn modulus(&self) -> Option<u64> {
+        Some(self.modulus)
+    }
+}
+
+impl Worry for BigUint {
+    fn with_value(&self, v: u64) -> BigUint {
+        BigUint::from(v)
+    }
+
+    fn apply(&self, operator: Operator, rhs: &BigUint) -> Option<BigUint> {
+        match operator {
+            Add => Some(self + rhs),
+            Sub => (self >= rhs).then(|| self - rhs),
+            Mul => Some(self * rhs),
+            Div => (*rhs != BigUint::ZERO).then(|| self / rhs),
         }
     }
 
+    fn is_divisible_by(&self, divisor: u64) -> bool {
+        (self % divisor) == BigUint::ZERO
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        self.clone()
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Backend {
+    CheckedU64,
+    U128,
+    Modular,
+    Arbitrary,
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn divisor_lcm(monkeys: &[Monkey]) -> Result<u64> {
     monkeys
         .iter()
-        .map(|monkey| monkey.num_inspected)
+        .try_fold(1u64, |lcm, monkey| {
+            let divisor = monkey.divisibility_test;
+            ensure!(divisor > 0, "Divisibility test by zero");
+            (lcm / gcd(lcm, divisor)).checked_mul(divisor).context("LCM of divisibility tests overflows u64")
+        })
+}
+
+fn modular_modulus(monkeys: &[Monkey]) -> Result<u64> {
+    ensure!(
+        monkeys.iter().all(|monkey| !monkey.operation.uses(Sub) && !monkey.operation.uses(Div)),
+        "Only + and * are compatible with modular worry levels",
+    );
+
+    divisor_lcm(monkeys)
+}
+
+fn monkey_business(inspected: impl IntoIterator<Item=u64>) -> u128 {
+    inspected
+        .into_iter()
         .sorted()
         .rev()
         .take(2)
+        .map(|count| count as u128)
         .product()
 }
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Throw {
+    pub round: usize,
+    pub from: usize,
+    pub to: usize,
+    pub before: BigUint,
+    pub after: BigUint,
+    pub modulus: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Log {
+    pub throws: Vec<Throw>,
+    pub inspections: Vec<Vec<u64>>,
+}
+
+impl Log {
+    fn cumulative(&self) -> impl Iterator<Item=Vec<u64>> + '_ {
+        self.inspections
+            .iter()
+            .scan(vec![], |totals: &mut Vec<u64>, round| {
+                totals.resize(round.len(), 0);
+                totals.iter_mut().zip(round).for_each(|(total, count)| *total += count);
+                Some(totals.clone())
+            })
+    }
+
+    pub fn throws_csv(&self) -> String {
+        let mut output = String::from("round,from,to,before,after,modulus\n");
+
+        for throw in &self.throws {
+            let modulus = throw.modulus.map(|modulus| modulus.to_string()).unwrap_or_default();
+            output.push_str(&format!("{},{},{},{},{},{}\n", throw.round, throw.from, throw.to, throw.before, throw.after, modulus));
+        }
+
+        output
+    }
+
+    pub fn inspections_csv(&self) -> String {
+        let num_monkeys = self.inspections.first().map_or(0, Vec::len);
+        let mut output = std::iter::once("round".to_string())
+            .chain((0..num_monkeys).map(|i| format!("monkey_{}", i)))
+            .chain(std::iter::once("monkey_business".to_string()))
+            .join(",") + "\n";
+
+        for (round, (counts, totals)) in self.inspections.iter().zip(self.cumulative()).enumerate() {
+            output.push_str(&format!("{},{},{}\n", round + 1, counts.iter().join(","), monkey_business(totals)));
+        }
+
+        output
+    }
+
+    pub fn summary(&self, rounds: &[usize]) -> String {
+        self.cumulative()
+            .enumerate()
+            .filter(|(round, _)| rounds.contains(&(round + 1)))
+            .map(|(round, totals)| {
+                let lines = totals
+                    .iter()
+                    .enumerate()
+                    .map(|(i, total)| format!("Monkey {} inspected items {} times.\n", i, total))
+                    .join("");
+                format!("== After round {} ==\n{}", round + 1, lines)
+            })
+            .join("\n")
+    }
+}
+
+fn simulate<W: Worry>(monkeys: &[Monkey], rounds: usize, relief: u64, lift: impl Fn(u64) -> W, mut log: Option<&mut Log>) -> Result<Vec<u64>> {
+    let mut items = monkeys
+        .iter()
+        .map(|monkey| monkey.items.iter().map(|&item| lift(item)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut inspected = vec![0; monkeys.len()];
+
+    for round in 1..=rounds {
+        let before_round = inspected.clone();
+
+        for (i, monkey) in monkeys.iter().enumerate() {
+            for mut item in std::mem::take(&mut items[i]) {
+                let before = log.is_some().then(|| item.to_biguint());
+                item = monkey.operation.evaluate(&item)?;
+                if relief != 1 {
+                    item = item.apply(Div, &item.with_value(relief)).context("Division by zero relief")?;
+                }
+                let target = if item.is_divisible_by(monkey.divisibility_test) {
+                    monkey.target_if_true
+                } else {
+                    monkey.target_if_false
+                };
+                if let (Some(log), Some(before)) = (log.as_deref_mut(), before) {
+                    log.throws.push(Throw { round, from: i, to: target, before, after: item.to_biguint(), modulus: item.modulus() });
+                }
+                items.get_mut(target).with_context(|| format!("Monkey {} throws to unknown monkey {}", i, target))?.push(item);
+                inspected[i] += 1;
+            }
+        }
+
+        if let Some(log) = log.as_deref_mut() {
+            log.inspections.push(inspected.iter().zip(before_round).map(|(after, before)| after - before).collect());
+        }
+    }
+
+    Ok(inspected)
+}
+
+pub fn solve(monkeys: &[Monkey], rounds: usize, relief: u64, backend: Backend, log: Option<&mut Log>) -> Result<usize> {
+    let inspected = match backend {
+        Backend::CheckedU64 => simulate(monkeys, rounds, relief, |item| item, log),
+        Backend::U128 => simulate(monkeys, rounds, relief, |item| item as u128, log),
+        Backend::Modular => {
+            ensure!(relief == 1, "Relief by division is not compatible with modular worry levels");
+            let modulus = modular_modulus(monkeys)?;
+            simulate(monkeys, rounds, relief, |item| Modular { value: item % modulus, modulus }, log)
+        },
+        Backend::Arbitrary => simulate(monkeys, rounds, relief, BigUint::from, log),
+    }?;
+
+    usize::try_from(monkey_business(inspected)).context("Monkey business overflows usize")
+}
+
+fn trace_item(monkeys: &[Monkey], mut position: usize, mut item: Modular, rounds: u64) -> Result<Vec<u64>> {
+    let mut seen = HashMap::new();
+    let mut history = vec![vec![0u64; monkeys.len()]];
+
+    for round in 0..rounds {
+        if let Some(&first) = seen.get(&(position, item.value)) {
+            let period = round - first;
+            let (cycles, remainder) = ((rounds - round) / period, (rounds - round) % period);
+            let (start, end, partial) = (&history[first as usize], &history[round as usize], &history[(first + remainder) as usize]);
+
+            return (0..monkeys.len())
+                .map(|i| {
+                    (end[i] - start[i])
+                        .checked_mul(cycles)
+                        .and_then(|repeated| (end[i] + partial[i] - start[i]).checked_add(repeated))
+                        .context("Inspection count overflows u64")
+                })
+                .collect();
+        }
+        seen.insert((position, item.value), round);
+
+        let mut inspected = history[round as usize].clone();
+        let mut current = position;
+        position = loop {
+            let monkey = monkeys.get(current).with_context(|| format!("Unknown monkey {}", current))?;
+            inspected[current] += 1;
+            item = monkey.operation.evaluate(&item)?;
+            let target = if item.is_divisible_by(monkey.divisibility_test) {
+                monkey.target_if_true
+            } else {
+                monkey.target_if_false
+            };
+
+            if target <= current {
+                break target;
+            }
+            current = target;
+        };
+        history.push(inspected);
+    }
+
+    Ok(history.pop().unwrap_or_default())
+}
+
+pub fn extrapolate(monkeys: &[Monkey], rounds: u64) -> Result<Vec<u64>> {
+    let modulus = modular_modulus(monkeys)?;
+    let mut inspected = vec![0u64; monkeys.len()];
+
+    for (position, monkey) in monkeys.iter().enumerate() {
+        for &item in &monkey.items {
+            let counts = trace_item(monkeys, position, Modular { value: item % modulus, modulus }, rounds)?;
+            for (total, count) in inspected.iter_mut().zip(counts) {
+                *total = total.checked_add(count).context("Inspection count overflows u64")?;
+            }
+        }
+    }
+
+    Ok(inspected)
+}
+
 #[aoc(day11, part1)]
-fn part1(monkeys: &[Monkey]) -> usize {
-    solve(monkeys, 20, |item| item / 3)
+fn part1(monkeys: &[Monkey]) -> Result<usize> {
+    solve(monkeys, 20, 3, Backend::CheckedU64, None)
 }
 
 #[aoc(day11, part2)]
-fn part2(monkeys: &[Monkey]) -> usize {
-    let modulus: u64 = monkeys.iter().map(|m| m.divisibility_test).product();
-    solve(monkeys, 10000, |item| item % modulus)
+fn part2(monkeys: &[Monkey]) -> Result<u128> {
+    Ok(monkey_business(extrapolate(monkeys, 10000)?))
 }
 
 #[cfg(test)]
@@ -119,21 +566,180 @@ mod tests {
 
     #[test]
     fn part1_example1() {
-        assert_eq!(10605, part1(&parse(include_str!("../input/2022/day11.part1.test.10605.txt")).unwrap()));
+        assert_eq!(10605, part1(&parse(include_str!("../input/2022/day11.part1.test.10605.txt")).unwrap()).unwrap());
     }
 
     #[test]
     fn part1_input() {
-        assert_eq!(54054, part1(&parse(include_str!("../input/2022/day11.txt")).unwrap()));
+        assert_eq!(54054, part1(&parse(include_str!("../input/2022/day11.txt")).unwrap()).unwrap());
     }
 
     #[test]
     fn part2_example1() {
-        assert_eq!(2713310158, part2(&parse(include_str!("../input/2022/day11.part2.test.2713310158.txt")).unwrap()));
+        assert_eq!(2713310158, part2(&parse(include_str!("../input/2022/day11.part2.test.2713310158.txt")).unwrap()).unwrap());
     }
 
     #[test]
     fn part2_input() {
-        assert_eq!(14314925001, part2(&parse(include_str!("../input/2022/day11.txt")).unwrap()));
+        assert_eq!(14314925001, part2(&parse(include_str!("../input/2022/day11.txt")).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn parse_expressions() {
+        let old = || Box::new(Expression::Old);
+        let literal = |v| Box::new(Expression::Literal(v));
+
+        assert_eq!(Expression::Binary(Mul, old(), old()), "new = old * old".parse().unwrap());
+        assert_eq!(Expression::Binary(Sub, literal(100), old()), "new = 100 - old".parse().unwrap());
+        assert_eq!(
+            Expression::Binary(Add, literal(1), Box::new(Expression::Binary(Mul, literal(2), old()))),
+            "new = 1 + 2 * old".parse().unwrap(),
+        );
+        assert_eq!(
+            Expression::Binary(Mul, Box::new(Expression::Binary(Add, literal(1), literal(2))), old()),
+            "new = (1 + 2) * old".parse().unwrap(),
+        );
+        assert!("new = old ^ 2".parse::<Expression>().is_err());
+        assert!("new = (old + 2".parse::<Expression>().is_err());
+        assert!("new = old old".parse::<Expression>().is_err());
+        assert!("old * 2".parse::<Expression>().is_err());
+    }
+
+    #[test]
+    fn evaluate_expressions() {
+        let evaluate = |s: &str, old: u64| s.parse::<Expression>().unwrap().evaluate(&old);
+
+        assert_eq!(17, evaluate("new = (old - 2) * old / 3 + 1", 8).unwrap());
+        assert_eq!(7, evaluate("new = 100 / (old + 10) - 2", 1).unwrap());
+        assert_eq!("Worry level underflow evaluating 1 - 2", evaluate("new = old - 2", 1).unwrap_err().to_string());
+        assert_eq!("Division by zero evaluating 3 / 0", evaluate("new = 3 / (old - 1)", 1).unwrap_err().to_string());
+        assert_eq!("Worry level overflow evaluating 9223372036854775807 * 9223372036854775807", evaluate("new = old * old", u64::MAX / 2).unwrap_err().to_string());
+        assert_eq!("Worry level overflow evaluating 18446744073709551615 + 1", evaluate("new = old + 1", u64::MAX).unwrap_err().to_string());
+    }
+
+    #[test]
+    fn solve_with_general_operations() {
+        let input = include_str!("../input/2022/day11.part1.test.10605.txt")
+            .replace("new = old * 19", "new = 19 * old")
+            .replace("new = old + 6", "new = (old + 12) - 6")
+            .replace("new = old * old", "new = old * (old)");
+
+        assert_eq!(10605, part1(&parse(&input).unwrap()).unwrap());
+        assert!(solve(&parse(&input.replace("new = old + 3", "new = old - 100")).unwrap(), 20, 3, Backend::CheckedU64, None).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn backends_agree() {
+        let monkeys = parse(include_str!("../input/2022/day11.part1.test.10605.txt")).unwrap();
+
+        for backend in [Backend::CheckedU64, Backend::U128, Backend::Arbitrary] {
+            assert_eq!(10605, solve(&monkeys, 20, 3, backend, None).unwrap());
+        }
+        for backend in [Backend::CheckedU64, Backend::U128, Backend::Modular, Backend::Arbitrary] {
+            assert_eq!(2500, solve(&monkeys, 10, 1, backend, None).unwrap());
+        }
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let monkeys = parse(include_str!("../input/2022/day11.part1.test.10605.txt")).unwrap();
+
+        let error = solve(&monkeys, 13, 1, Backend::CheckedU64, None).unwrap_err();
+        assert!(error.to_string().starts_with("Worry level overflow"));
+        assert!(solve(&monkeys, 13, 1, Backend::U128, None).is_ok());
+        assert!(solve(&monkeys, 1000, 1, Backend::U128, None).is_err());
+        assert!(solve(&monkeys, 1000, 3, Backend::Modular, None).is_err());
+    }
+
+    #[test]
+    fn modulus_is_lcm() {
+        let input = include_str!("../input/2022/day11.part1.test.10605.txt")
+            .replace("divisible by 23", "divisible by 26")
+            .replace("divisible by 19", "divisible by 39")
+            .replace("divisible by 17", "divisible by 4");
+        let monkeys = parse(&input).unwrap();
+
+        assert_eq!(2 * 2 * 3 * 13, divisor_lcm(&monkeys).unwrap());
+        assert_eq!(960, solve(&monkeys, 6, 1, Backend::U128, None).unwrap());
+        assert_eq!(960, solve(&monkeys, 6, 1, Backend::Modular, None).unwrap());
+    }
+
+    #[test]
+    fn divisibility_by_zero_is_rejected() {
+        let input = include_str!("../input/2022/day11.part1.test.10605.txt").replace("divisible by 19", "divisible by 0");
+        assert_eq!("Divisibility test by zero", parse(&input).unwrap_err().to_string());
+    }
+
+    #[test]
+    fn extrapolate_matches_simulation() {
+        for input in [include_str!("../input/2022/day11.part2.test.2713310158.txt"), include_str!("../input/2022/day11.txt")] {
+            let monkeys = parse(input).unwrap();
+            let modulus = modular_modulus(&monkeys).unwrap();
+
+            for rounds in [0, 1, 20, 1000, 10000] {
+                let simulated = simulate(&monkeys, rounds, 1, |item| Modular { value: item % modulus, modulus }, None).unwrap();
+                assert_eq!(simulated, extrapolate(&monkeys, rounds as u64).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn extrapolate_many_rounds() {
+        let monkeys = parse(include_str!("../input/2022/day11.part2.test.2713310158.txt")).unwrap();
+        let modulus = modular_modulus(&monkeys).unwrap();
+        let simulated = simulate(&monkeys, 100_000, 1, |item| Modular { value: item % modulus, modulus }, None).unwrap();
+        assert_eq!(simulated, extrapolate(&monkeys, 100_000).unwrap());
+
+        let inspected = extrapolate(&monkeys, 1_000_000_000_000).unwrap();
+        assert_eq!(vec![5217653508757, 4782346491239, 193256578955, 5202028508760], inspected);
+        assert_eq!(27142382301385558311211320, monkey_business(inspected));
+    }
+
+    #[test]
+    fn summary_example() {
+        let monkeys = parse(include_str!("../input/2022/day11.part2.test.2713310158.txt")).unwrap();
+        let mut log = Log::default();
+        solve(&monkeys, 1000, 1, Backend::Modular, Some(&mut log)).unwrap();
+        let expected = [
+            "== After round 1 ==",
+            "Monkey 0 inspected items 2 times.",
+            "Monkey 1 inspected items 4 times.",
+            "Monkey 2 inspected items 3 times.",
+            "Monkey 3 inspected items 6 times.",
+            "",
+            "== After round 20 ==",
+            "Monkey 0 inspected items 99 times.",
+            "Monkey 1 inspected items 97 times.",
+            "Monkey 2 inspected items 8 times.",
+            "Monkey 3 inspected items 103 times.",
+            "",
+            "== After round 1000 ==",
+            "Monkey 0 inspected items 5204 times.",
+            "Monkey 1 inspected items 4792 times.",
+            "Monkey 2 inspected items 199 times.",
+            "Monkey 3 inspected items 5192 times.",
+        ].join("\n") + "\n";
+
+        assert_eq!(expected, log.summary(&[1, 20, 1000]));
+        assert_eq!(1000, log.inspections.len());
+        assert_eq!(Some("1,0,3,79,1501,96577"), log.throws_csv().lines().nth(1));
+        assert!(log.throws_csv().lines().skip(1).all(|line| line.split(',').all(|cell| cell.parse::<u64>().is_ok())));
+    }
+
+    #[test]
+    fn throw_log_csv() {
+        let monkeys = parse(include_str!("../input/2022/day11.part1.test.10605.txt")).unwrap();
+        let mut log = Log::default();
+        solve(&monkeys, 20, 3, Backend::CheckedU64, Some(&mut log)).unwrap();
+        let throws = log.throws_csv();
+        let inspections = log.inspections_csv();
+
+        assert_eq!(Some("round,from,to,before,after,modulus"), throws.lines().next());
+        assert_eq!(Some("1,0,3,79,500,"), throws.lines().nth(1));
+        assert_eq!(Some("1,0,3,98,620,"), throws.lines().nth(2));
+        assert_eq!(Some("round,monkey_0,monkey_1,monkey_2,monkey_3,monkey_business"), inspections.lines().next());
+        assert_eq!(Some("1,2,4,3,5,20"), inspections.lines().nth(1));
+        assert_eq!(Some("20,6,4,0,6,10605"), inspections.lines().last());
+        assert_eq!(log.inspections.iter().flatten().sum::<u64>() as usize, log.throws.len());
+    }
+}
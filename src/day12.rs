@@ -1,12 +1,14 @@
 use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap};
-use anyhow::{Result, Context};
+use std::fmt;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::collections::hash_map::Entry;
+use anyhow::{bail, Result, Context};
 use aoc_runner_derive::{aoc, aoc_generator};
 
-type Input = ((i32, i32), (i32, i32), HashMap<(i32, i32), i32>);
+pub type Input = ((i32, i32), (i32, i32), HashMap<(i32, i32), i32>);
 
 #[aoc_generator(day12)]
-fn parse(input: &str) -> Result<Input> {
+pub fn parse(input: &str) -> Result<Input> {
     let width = input.lines().next().context("Incorrect input format")?.len() as i32;
 
     let (source, target, grid) = input
@@ -32,23 +34,49 @@ fn parse(input: &str) -> Result<Input> {
     ))
 }
 
-fn neighbors((i, j): (i32, i32), grid: &HashMap<(i32, i32), i32>) -> impl IntoIterator<Item=(i32, i32)> {
-    let candidates = vec![
-        (i - 1, j),
-        (i + 1, j),
-        (i, j - 1),
-        (i, j + 1),
-    ];
+pub trait ClimbingRule {
+    fn can_step(&self, from: i32, to: i32) -> bool;
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MaxClimb(pub i32);
+
+impl ClimbingRule for MaxClimb {
+    fn can_step(&self, from: i32, to: i32) -> bool {
+        to <= from + self.0
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MaxSlope(pub i32);
 
-    candidates
+impl ClimbingRule for MaxSlope {
+    fn can_step(&self, from: i32, to: i32) -> bool {
+        (to - from).abs() <= self.0
+    }
+}
+
+fn adjacent((i, j): (i32, i32), grid: &HashMap<(i32, i32), i32>) -> impl Iterator<Item=(i32, i32)> + '_ {
+    [(i - 1, j), (i + 1, j), (i, j - 1), (i, j + 1)]
         .into_iter()
         .filter(|candidate| grid.contains_key(candidate))
-        .filter(|candidate| grid[candidate] <= grid[&(i, j)] + 1)
-        .collect::<Vec<_>>()
 }
 
-fn distance(sources: &[(i32, i32)], target: &(i32, i32), grid: &HashMap<(i32, i32), i32>) -> Option<i32> {
+fn neighbors(position: (i32, i32), grid: &HashMap<(i32, i32), i32>, rule: &impl ClimbingRule) -> Vec<(i32, i32)> {
+    adjacent(position, grid)
+        .filter(|candidate| rule.can_step(grid[&position], grid[candidate]))
+        .collect()
+}
+
+fn predecessors(position: (i32, i32), grid: &HashMap<(i32, i32), i32>, rule: &impl ClimbingRule) -> Vec<(i32, i32)> {
+    adjacent(position, grid)
+        .filter(|candidate| rule.can_step(grid[candidate], grid[&position]))
+        .collect()
+}
+
+pub fn shortest_path(sources: &[(i32, i32)], target: &(i32, i32), grid: &HashMap<(i32, i32), i32>, rule: &impl ClimbingRule) -> Option<Vec<(i32, i32)>> {
     let mut distances: HashMap<(i32, i32), i32> = HashMap::new();
+    let mut previous: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
     let mut queue: BinaryHeap<(Reverse<i32>, (i32, i32))> = BinaryHeap::new();
 
     for source in sources {
@@ -61,17 +89,154 @@ fn distance(sources: &[(i32, i32)], target: &(i32, i32), grid: &HashMap<(i32, i3
             break;
         }
 
-        for neighbor in neighbors(position, grid) {
+        for neighbor in neighbors(position, grid, rule) {
             let neighbor_distance = distances.entry(neighbor).or_insert(i32::MAX);
 
             if *neighbor_distance > distance + 1 {
                 *neighbor_distance = distance + 1;
+                previous.insert(neighbor, position);
                 queue.push((Reverse(*neighbor_distance), neighbor));
             }
         }
     }
 
-    distances.get(target).copied()
+    distances.get(target)?;
+
+    let mut path = vec![*target];
+    while let Some(position) = previous.get(path.last()?) {
+        path.push(*position);
+    }
+    path.reverse();
+
+    Some(path)
+}
+
+fn distance(sources: &[(i32, i32)], target: &(i32, i32), grid: &HashMap<(i32, i32), i32>) -> Option<i32> {
+    shortest_path(sources, target, grid, &MaxClimb(1)).map(|path| path.len() as i32 - 1)
+}
+
+#[derive(Debug, Clone)]
+pub struct DistanceField {
+    pub target: (i32, i32),
+    distances: HashMap<(i32, i32), i32>,
+    next: HashMap<(i32, i32), (i32, i32)>,
+    closest: HashMap<i32, ((i32, i32), i32)>,
+}
+
+impl DistanceField {
+    pub fn new(target: (i32, i32), grid: &HashMap<(i32, i32), i32>, rule: &impl ClimbingRule) -> DistanceField {
+        let mut distances = HashMap::from([(target, 0)]);
+        let mut next = HashMap::new();
+        let mut queue = VecDeque::from([target]);
+
+        while let Some(position) = queue.pop_front() {
+            let distance = distances[&position];
+
+            for predecessor in predecessors(position, grid, rule) {
+                if let Entry::Vacant(entry) = distances.entry(predecessor) {
+                    entry.insert(distance + 1);
+                    next.insert(predecessor, position);
+                    queue.push_back(predecessor);
+                }
+            }
+        }
+
+        let mut closest: HashMap<i32, ((i32, i32), i32)> = HashMap::new();
+        for (&position, &distance) in &distances {
+            let entry = closest.entry(grid[&position]).or_insert((position, distance));
+            if (distance, position) < (entry.1, entry.0) {
+                *entry = (position, distance);
+            }
+        }
+
+        DistanceField { target, distances, next, closest }
+    }
+
+    pub fn distance(&self, position: (i32, i32)) -> Option<i32> {
+        self.distances.get(&position).copied()
+    }
+
+    pub fn path(&self, position: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+        self.distances.get(&position)?;
+
+        let mut path = vec![position];
+        while let Some(next) = self.next.get(path.last()?) {
+            path.push(*next);
+        }
+
+        Some(path)
+    }
+
+    pub fn closest(&self, elevation: char) -> Option<((i32, i32), i32)> {
+        self.closest.get(&(elevation as i32)).copied()
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BestStart {
+    pub start: (i32, i32),
+    pub distance: i32,
+}
+
+impl fmt::Display for BestStart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.distance)
+    }
+}
+
+pub fn best_start((_, target, grid): &Input) -> Option<BestStart> {
+    let (start, distance) = DistanceField::new(*target, grid, &MaxClimb(1)).closest('a')?;
+
+    Some(BestStart { start, distance })
+}
+
+pub fn best_route((_, target, grid): &Input) -> Option<Vec<(i32, i32)>> {
+    let field = DistanceField::new(*target, grid, &MaxClimb(1));
+    let (start, _) = field.closest('a')?;
+
+    field.path(start)
+}
+
+fn cell_mut(cells: &mut [Vec<char>], (i, j): (i32, i32)) -> Result<&mut char> {
+    usize::try_from(i).ok()
+        .zip(usize::try_from(j).ok())
+        .and_then(|(i, j)| cells.get_mut(i)?.get_mut(j))
+        .with_context(|| format!("Route leaves the map at row {}, column {}", i, j))
+}
+
+pub fn render_route(path: &[(i32, i32)], grid: &HashMap<(i32, i32), i32>, blank: Option<char>) -> Result<String> {
+    let height = grid.keys().map(|&(i, _)| i + 1).max().unwrap_or_default();
+    let width = grid.keys().map(|&(_, j)| j + 1).max().unwrap_or_default();
+    let mut cells = (0..height)
+        .map(|i| {
+            (0..width)
+                .map(|j| {
+                    let elevation = grid.get(&(i, j)).with_context(|| format!("Missing cell at row {}, column {}", i, j))?;
+                    Ok(blank.unwrap_or_else(|| char::from_u32(*elevation as u32).unwrap_or('?')))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for step in path.windows(2) {
+        let ((i, j), next) = (step[0], step[1]);
+        *cell_mut(&mut cells, (i, j))? = match (next.0 - i, next.1 - j) {
+            (-1, 0) => '^',
+            (1, 0) => 'v',
+            (0, -1) => '<',
+            (0, 1) => '>',
+            _ => bail!("Route jumps from {:?} to {:?}", step[0], step[1]),
+        };
+    }
+
+    if let Some(&end) = path.last() {
+        *cell_mut(&mut cells, end)? = 'E';
+    }
+
+    Ok(cells
+        .into_iter()
+        .map(|row| row.into_iter().collect::<String>() + "\n")
+        .collect())
 }
 
 #[aoc(day12, part1)]
@@ -80,15 +245,8 @@ fn part1((source, target, grid): &Input) -> i32 {
 }
 
 #[aoc(day12, part2)]
-fn part2((_, target, grid): &Input) -> i32 {
-    let sources = grid
-        .iter()
-        .filter(|(_, elevation)| **elevation == 'a' as i32)
-        .map(|(position, _)| position)
-        .copied()
-        .collect::<Vec<_>>();
-
-    distance(&sources, target, grid).unwrap()
+fn part2(input: &Input) -> BestStart {
+    best_start(input).unwrap()
 }
 
 #[cfg(test)]
@@ -107,11 +265,83 @@ mod tests {
 
     #[test]
     fn part2_example1() {
-        assert_eq!(29, part2(&parse(include_str!("../input/2022/day12.part2.test.29.txt")).unwrap()));
+        assert_eq!(BestStart { start: (4, 0), distance: 29 }, part2(&parse(include_str!("../input/2022/day12.part2.test.29.txt")).unwrap()));
     }
 
     #[test]
     fn part2_input() {
-        assert_eq!(522, part2(&parse(include_str!("../input/2022/day12.txt")).unwrap()));
+        assert_eq!(522, part2(&parse(include_str!("../input/2022/day12.txt")).unwrap()).distance);
+    }
+
+    #[test]
+    fn route_example1() {
+        let input = parse(include_str!("../input/2022/day12.part1.test.31.txt")).unwrap();
+        let (source, target, grid) = &input;
+        let path = shortest_path(&[*source], target, grid, &MaxClimb(1)).unwrap();
+
+        assert_eq!(32, path.len());
+        assert_eq!((source, target), (&path[0], &path[31]));
+        assert!(path.windows(2).all(|step| neighbors(step[0], grid, &MaxClimb(1)).into_iter().any(|neighbor| neighbor == step[1])));
+
+        let expected = [
+            "v..v<<<<",
+            ">v.vv<<^",
+            ".v.v>E^^",
+            ".>v>>>^^",
+            "..>>>>>^",
+        ].join("\n") + "\n";
+        assert_eq!(expected, render_route(&path, grid, Some('.')).unwrap());
+        assert!(render_route(&path, grid, None).unwrap().starts_with("vabv<<<<\n>vcvv<<^\n"));
+        assert_eq!(Some((4, 0)), best_route(&input).map(|route| route[0]));
+    }
+
+    #[test]
+    fn render_ragged_map() {
+        let (source, target, mut grid) = parse(include_str!("../input/2022/day12.part1.test.31.txt")).unwrap();
+        let path = shortest_path(&[source], &target, &grid, &MaxClimb(1)).unwrap();
+        grid.remove(&(4, 7));
+
+        assert_eq!("Missing cell at row 4, column 7", render_route(&path, &grid, None).unwrap_err().to_string());
+        assert!(render_route(&[(0, 0), (5, 0)], &grid, Some('.')).is_err());
+    }
+
+    #[test]
+    fn best_route_input() {
+        let input = parse(include_str!("../input/2022/day12.txt")).unwrap();
+        let path = best_route(&input).unwrap();
+
+        assert_eq!(523, path.len());
+        assert_eq!('a' as i32, input.2[&path[0]]);
+        assert_eq!(input.1, path[522]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn distance_field_example1() {
+        let (source, target, grid) = parse(include_str!("../input/2022/day12.part1.test.31.txt")).unwrap();
+        let field = DistanceField::new(target, &grid, &MaxClimb(1));
+
+        assert_eq!(Some(31), field.distance(source));
+        assert_eq!(Some(0), field.distance(target));
+        assert_eq!(Some(((4, 0), 29)), field.closest('a'));
+        assert_eq!(Some(((2, 5), 0)), field.closest('z'));
+        assert_eq!(None, field.closest('A'));
+        assert_eq!(32, field.path(source).unwrap().len());
+
+        for (&position, &elevation) in &grid {
+            let forward = shortest_path(&[position], &target, &grid, &MaxClimb(1)).map(|path| path.len() as i32 - 1);
+            assert_eq!(forward, field.distance(position), "{:?} ({})", position, elevation);
+        }
+    }
+
+    #[test]
+    fn climbing_rules() {
+        let (source, target, grid) = parse(include_str!("../input/2022/day12.part1.test.31.txt")).unwrap();
+
+        assert_eq!(None, DistanceField::new(target, &grid, &MaxClimb(0)).distance(source));
+        assert_eq!(Some(7), DistanceField::new(target, &grid, &MaxClimb(26)).distance(source));
+        assert_eq!(
+            shortest_path(&[source], &target, &grid, &MaxSlope(1)).map(|path| path.len() as i32 - 1),
+            DistanceField::new(target, &grid, &MaxSlope(1)).distance(source),
+        );
+    }
+}
@@ -1,9 +1,9 @@
-use std::cmp::max;
 use aoc_runner_derive::{aoc, aoc_generator};
 use anyhow::Result;
 use itertools::Itertools;
 use scan_fmt::scan_fmt;
 use rayon::prelude::*;
+use crate::interval::{Interval, IntervalSet};
 
 type Coordinate = (i64, i64);
 
@@ -22,7 +22,7 @@ fn distance((ax, ay): &Coordinate, (bx, by): &Coordinate) -> i64 {
     (ax - bx).abs() + (ay - by).abs()
 }
 
-fn reachable_ranges(input: &[(Coordinate, Coordinate)], y: i64) -> Vec<(i64, i64)> {
+fn reachable_ranges(input: &[(Coordinate, Coordinate)], y: i64) -> IntervalSet {
     input
         .iter()
         .copied()
@@ -34,27 +34,13 @@ fn reachable_ranges(input: &[(Coordinate, Coordinate)], y: i64) -> Vec<(i64, i64
                 return None;
             }
 
-            Some((sx - (d - dy), sx + (d - dy) + 1))
-        })
-        .sorted()
-        .fold(vec![], |mut acc, (a, b)| {
-            if let Some((_, pb)) = acc.last_mut() {
-                if a <= *pb {
-                    *pb = max(b, *pb);
-                    return acc;
-                }
-            }
-
-            acc.push((a, b));
-            acc
+            Interval::closed(sx - (d - dy), sx + (d - dy))
         })
+        .collect()
 }
 
 fn solve1(input: &[(Coordinate, Coordinate)], y: i64) -> i64 {
-    let num_reachable = reachable_ranges(input, y)
-        .iter()
-        .map(|(a, b)| b - a)
-        .sum::<i64>();
+    let num_reachable = reachable_ranges(input, y).coverage();
     let num_beacons = input
         .iter()
         .filter(|(_, (_, cy))| *cy == y)
@@ -72,12 +58,13 @@ fn part1(input: &[(Coordinate, Coordinate)]) -> i64 {
 }
 
 fn solve2(input: &[(Coordinate, Coordinate)], max: i64) -> Option<i64> {
+    let bounds = Interval::closed(0, max)?;
+
     (0..=max)
         .into_par_iter()
-        .map(|y| (y, reachable_ranges(input, y)))
-        .find_any(|(_, ranges)| ranges.len() > 1)
-        .map(|(y, ranges)| (ranges[0].1 as i64, y as i64))
-        .map(|(x, y)| x * 4000000 + y)
+        .map(|y| (y, reachable_ranges(input, y).gaps(bounds)))
+        .find_any(|(_, gaps)| !gaps.is_empty())
+        .map(|(y, gaps)| gaps.intervals()[0].start * 4000000 + y)
 }
 
 #[aoc(day15, part2)]
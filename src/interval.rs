@@ -0,0 +1,225 @@
+use std::cmp::{max, min};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Interval {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Interval {
+    pub fn half_open(start: i64, end: i64) -> Interval {
+        Interval { start, end }
+    }
+
+    pub fn closed(first: i64, last: i64) -> Option<Interval> {
+        Some(Interval { start: first, end: last.checked_add(1)? })
+    }
+
+    pub fn last(&self) -> i64 {
+        self.end - 1
+    }
+
+    pub fn len(&self) -> i64 {
+        max(self.end - self.start, 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+
+    pub fn contains_point(&self, x: i64) -> bool {
+        self.start <= x && x < self.end
+    }
+
+    pub fn contains(&self, other: &Interval) -> bool {
+        other.is_empty() || (self.start <= other.start && other.end <= self.end)
+    }
+
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    pub fn intersection(&self, other: &Interval) -> Interval {
+        Interval {
+            start: max(self.start, other.start),
+            end: min(self.end, other.end),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct IntervalSet {
+    intervals: Vec<Interval>,
+}
+
+impl IntervalSet {
+    pub fn new() -> IntervalSet {
+        IntervalSet::default()
+    }
+
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn coverage(&self) -> i64 {
+        self.intervals.iter().map(Interval::len).sum()
+    }
+
+    pub fn contains_point(&self, x: i64) -> bool {
+        let i = self.intervals.partition_point(|interval| interval.end <= x);
+        self.intervals.get(i).is_some_and(|interval| interval.contains_point(x))
+    }
+
+    pub fn insert(&mut self, interval: Interval) {
+        *self = self.union(&IntervalSet::from(interval));
+    }
+
+    pub fn union(&self, other: &IntervalSet) -> IntervalSet {
+        self.intervals.iter().chain(&other.intervals).copied().collect()
+    }
+
+    pub fn intersection(&self, other: &IntervalSet) -> IntervalSet {
+        let mut intervals = vec![];
+        let (mut i, mut j) = (0, 0);
+
+        while let (Some(a), Some(b)) = (self.intervals.get(i), other.intervals.get(j)) {
+            let overlap = a.intersection(b);
+            if !overlap.is_empty() {
+                intervals.push(overlap);
+            }
+
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        IntervalSet { intervals }
+    }
+
+    pub fn difference(&self, other: &IntervalSet) -> IntervalSet {
+        let mut intervals = vec![];
+        let mut j = 0;
+
+        for &a in &self.intervals {
+            let mut start = a.start;
+
+            while let Some(b) = other.intervals.get(j) {
+                if b.end <= start {
+                    j += 1;
+                    continue;
+                }
+
+                if b.start >= a.end {
+                    break;
+                }
+
+                if b.start > start {
+                    intervals.push(Interval::half_open(start, b.start));
+                }
+
+                start = b.end;
+
+                if b.end > a.end {
+                    break;
+                }
+
+                j += 1;
+            }
+
+            if start < a.end {
+                intervals.push(Interval::half_open(start, a.end));
+            }
+        }
+
+        IntervalSet { intervals }
+    }
+
+    pub fn gaps(&self, bounds: Interval) -> IntervalSet {
+        IntervalSet::from(bounds).difference(self)
+    }
+}
+
+impl From<Interval> for IntervalSet {
+    fn from(interval: Interval) -> IntervalSet {
+        std::iter::once(interval).collect()
+    }
+}
+
+impl FromIterator<Interval> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item=Interval>>(iter: T) -> IntervalSet {
+        let mut sorted = iter.into_iter()
+            .filter(|interval| !interval.is_empty())
+            .collect::<Vec<_>>();
+        sorted.sort();
+
+        let intervals = sorted
+            .into_iter()
+            .fold(Vec::<Interval>::new(), |mut acc, interval| {
+                if let Some(previous) = acc.last_mut() {
+                    if interval.start <= previous.end {
+                        previous.end = max(previous.end, interval.end);
+                        return acc;
+                    }
+                }
+
+                acc.push(interval);
+                acc
+            });
+
+        IntervalSet { intervals }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(intervals: &[(i64, i64)]) -> IntervalSet {
+        intervals.iter().map(|&(a, b)| Interval::half_open(a, b)).collect()
+    }
+
+    #[test]
+    fn closed_and_half_open() {
+        assert_eq!(Interval::half_open(2, 5), Interval::closed(2, 4).unwrap());
+        assert_eq!(3, Interval::closed(2, 4).unwrap().len());
+        assert_eq!(4, Interval::closed(2, 4).unwrap().last());
+        assert!(Interval::closed(2, 4).unwrap().overlaps(&Interval::closed(4, 6).unwrap()));
+        assert!(!Interval::half_open(2, 4).overlaps(&Interval::half_open(4, 6)));
+        assert!(Interval::closed(2, 8).unwrap().contains(&Interval::closed(3, 7).unwrap()));
+        assert_eq!(None, Interval::closed(0, i64::MAX));
+    }
+
+    #[test]
+    fn merge_on_collect() {
+        assert_eq!(vec![Interval::half_open(0, 6), Interval::half_open(7, 9)], set(&[(4, 6), (0, 2), (7, 9), (2, 4), (5, 5)]).intervals());
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = set(&[(0, 10), (20, 30)]);
+        let b = set(&[(5, 25), (28, 40)]);
+
+        assert_eq!(set(&[(0, 40)]), a.union(&b));
+        assert_eq!(set(&[(5, 10), (20, 25), (28, 30)]), a.intersection(&b));
+        assert_eq!(set(&[(0, 5), (25, 28)]), a.difference(&b));
+        assert_eq!(set(&[(10, 20), (30, 40)]), b.difference(&a));
+        assert_eq!(set(&[(-5, 0), (10, 20), (30, 35)]), a.gaps(Interval::half_open(-5, 35)));
+        assert_eq!(20, a.coverage());
+    }
+
+    #[test]
+    fn contains_point() {
+        let a = set(&[(0, 10), (20, 30)]);
+
+        assert!(a.contains_point(0));
+        assert!(!a.contains_point(10));
+        assert!(a.contains_point(29));
+        assert!(!a.contains_point(30));
+    }
+}
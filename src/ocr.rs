@@ -1,9 +1,11 @@
 use std::collections::HashMap;
-use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use anyhow::{bail, ensure, Context, Result};
 use lazy_static::lazy_static;
 
 const CHAR_WIDTH: usize = 5;
-const CHAR_HEIGHT: usize = 6;
+pub const CHAR_HEIGHT: usize = 6;
 const KNOWN_CHARS: &str = "ABCEFGHIJKLOPRSUZ";
 
 lazy_static! {
@@ -48,3 +50,255 @@ pub fn ocr(lit_pixel: char, input: &str) -> Result<String> {
 
     Ok(resolved_characters.iter().collect())
 }
+
+pub fn render_text(lit_pixel: char, text: &str) -> Result<String> {
+    let line_len = CHAR_WIDTH * KNOWN_CHARS.len();
+    let indices = text
+        .chars()
+        .map(|c| KNOWN_CHARS.find(c).with_context(|| format!("Character not in font: {}", c)))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((0..CHAR_HEIGHT)
+        .flat_map(|row| indices.iter().flat_map(move |&index| {
+            let start = row * line_len + index * CHAR_WIDTH;
+            KNOWN_CHARS_RENDERED[start..(start + CHAR_WIDTH)].chars()
+        }))
+        .map(|c| if c == '#' { lit_pixel } else { '.' })
+        .collect())
+}
+
+struct NetpbmReader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> NetpbmReader<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(&b) = self.input.get(self.position) {
+            match b {
+                b'#' => {
+                    while self.input.get(self.position).is_some_and(|&b| b != b'\n') {
+                        self.position += 1;
+                    }
+                },
+                b if b.is_ascii_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<&'a [u8]> {
+        self.skip_whitespace();
+        let start = self.position;
+
+        while self.input.get(self.position).is_some_and(|b| !b.is_ascii_whitespace() && *b != b'#') {
+            self.position += 1;
+        }
+
+        ensure!(start < self.position, "Unexpected end of Netpbm data");
+        Ok(&self.input[start..self.position])
+    }
+
+    fn number(&mut self) -> Result<usize> {
+        let token = self.token()?;
+        std::str::from_utf8(token)?
+            .parse()
+            .with_context(|| format!("Invalid number in Netpbm data: {}", String::from_utf8_lossy(token)))
+    }
+
+    fn plain_bit(&mut self) -> Result<bool> {
+        self.skip_whitespace();
+        let bit = match self.input.get(self.position) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            Some(&b) => bail!("Invalid PBM pixel: {}", b as char),
+            None => bail!("Unexpected end of Netpbm data"),
+        };
+        self.position += 1;
+
+        Ok(bit)
+    }
+
+    fn raster(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.input.get(self.position).is_some_and(u8::is_ascii_whitespace), "Expected whitespace before Netpbm raster");
+        let start = self.position + 1;
+        let raster = start.checked_add(len).and_then(|end| self.input.get(start..end)).context("Truncated Netpbm raster")?;
+        self.position = start + len;
+
+        Ok(raster)
+    }
+}
+
+pub fn read_netpbm(input: &[u8], threshold: u16) -> Result<(usize, String)> {
+    let mut reader = NetpbmReader { input, position: 0 };
+    let format = reader.token()?;
+    let width = reader.number()?;
+    let height = reader.number()?;
+    let max_value = match format {
+        b"P2" | b"P5" => reader.number()?,
+        _ => 1,
+    };
+    ensure!((1..=u16::MAX as usize).contains(&max_value), "Invalid Netpbm maximum value: {}", max_value);
+    let pixels = width.checked_mul(height).context("Netpbm image is too large")?;
+    let sample = |value: usize| {
+        ensure!(value <= max_value, "Netpbm sample {} exceeds maximum value {}", value, max_value);
+        Ok(value < threshold as usize)
+    };
+
+    // PBM stores ink as 1; greyscale pixels darker than the threshold count as ink as well
+    let lit = match format {
+        b"P1" => (0..pixels)
+            .map(|_| reader.plain_bit())
+            .collect::<Result<Vec<_>>>()?,
+        b"P2" => (0..pixels)
+            .map(|_| sample(reader.number()?))
+            .collect::<Result<Vec<_>>>()?,
+        b"P4" => {
+            let row_len = width.div_ceil(8);
+            let raster = reader.raster(row_len.checked_mul(height).context("Netpbm image is too large")?)?;
+            (0..pixels)
+                .map(|i| (i / width, i % width))
+                .map(|(y, x)| raster[y * row_len + x / 8] & (0x80 >> (x % 8)) != 0)
+                .collect()
+        },
+        b"P5" => {
+            let sample_len = if max_value < 256 { 1 } else { 2 };
+            let raster = reader.raster(pixels.checked_mul(sample_len).context("Netpbm image is too large")?)?;
+            raster
+                .chunks(sample_len)
+                .map(|bytes| sample(bytes.iter().fold(0, |acc, &b| (acc << 8) | b as usize)))
+                .collect::<Result<Vec<_>>>()?
+        },
+        _ => bail!("Unsupported Netpbm format: {}", String::from_utf8_lossy(format)),
+    };
+
+    Ok((width, lit.iter().map(|&lit| if lit { '#' } else { '.' }).collect()))
+}
+
+pub fn ocr_netpbm(input: &[u8], threshold: u16) -> Result<String> {
+    let (width, pixels) = read_netpbm(input, threshold)?;
+    ensure!(width % CHAR_WIDTH == 0, "Image width {} is not a multiple of {}", width, CHAR_WIDTH);
+    ensure!(pixels.len() == width * CHAR_HEIGHT, "Image height is not {}", CHAR_HEIGHT);
+
+    ocr('#', &pixels)
+}
+
+pub fn ocr_file(path: impl AsRef<Path>, threshold: u16) -> Result<String> {
+    let path = path.as_ref();
+    let input = fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+
+    ocr_netpbm(&input, threshold)
+}
+
+pub fn to_pbm(lit_pixel: char, input: &str, width: usize) -> Result<String> {
+    let pixels = input.chars().collect::<Vec<_>>();
+    ensure!(width > 0, "Image width must be positive");
+    ensure!(pixels.len().is_multiple_of(width), "{} pixels do not fill rows of width {}", pixels.len(), width);
+
+    let mut output = format!("P1\n{} {}\n", width, pixels.len() / width);
+
+    for row in pixels.chunks(width) {
+        let row = row.iter()
+            .map(|&c| if c == lit_pixel { "1" } else { "0" })
+            .collect::<Vec<_>>()
+            .join(" ");
+        output.push_str(&row);
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RENDERED: &str = concat!(
+        "#..#.####.",
+        "#..#.#....",
+        "####.###..",
+        "#..#.#....",
+        "#..#.#....",
+        "#..#.####.",
+    );
+
+    #[test]
+    fn ocr_plain_pbm() {
+        assert_eq!("HE", ocr_netpbm(to_pbm('#', RENDERED, 10).unwrap().as_bytes(), 1).unwrap());
+    }
+
+    #[test]
+    fn ocr_plain_pbm_with_comments() {
+        let input = to_pbm('#', RENDERED, 10).unwrap().replacen("P1\n", "P1\n# captured screen\n", 1);
+        assert_eq!("HE", ocr_netpbm(input.as_bytes(), 1).unwrap());
+    }
+
+    #[test]
+    fn ocr_raw_pbm() {
+        let mut input = b"P4\n10 6\n".to_vec();
+        for row in RENDERED.as_bytes().chunks(10) {
+            let bits = row.iter().fold(0u16, |acc, &c| (acc << 1) | (c == b'#') as u16) << 6;
+            input.extend(bits.to_be_bytes());
+        }
+
+        assert_eq!("HE", ocr_netpbm(&input, 1).unwrap());
+    }
+
+    #[test]
+    fn ocr_plain_pgm() {
+        let pixels = RENDERED.chars().map(|c| if c == '#' { "12" } else { "240" }).collect::<Vec<_>>();
+        let input = format!("P2\n10 6\n255\n{}\n", pixels.join(" "));
+
+        assert_eq!("HE", ocr_netpbm(input.as_bytes(), 128).unwrap());
+    }
+
+    #[test]
+    fn ocr_raw_pgm() {
+        let mut input = b"P5 10 6 65535\n".to_vec();
+        for c in RENDERED.chars() {
+            input.extend(if c == '#' { 1000u16 } else { 50000u16 }.to_be_bytes());
+        }
+
+        assert_eq!("HE", ocr_netpbm(&input, 32768).unwrap());
+    }
+
+    #[test]
+    fn to_pbm_invalid_width() {
+        assert!(to_pbm('#', RENDERED, 0).is_err());
+        assert!(to_pbm('#', RENDERED, 7).is_err());
+        assert!(to_pbm('#', "", 3).is_ok());
+    }
+
+    #[test]
+    fn read_raster_after_comment() {
+        let mut input = b"P5 10 6 255#comment\n".to_vec();
+        input.extend([0u8; 60]);
+
+        assert!(read_netpbm(&input, 128).is_err());
+    }
+
+    #[test]
+    fn read_truncated_raster() {
+        assert!(read_netpbm(b"P5 10 6 255\n\x00\x00", 128).is_err());
+    }
+
+    #[test]
+    fn read_oversized_image() {
+        assert_eq!("Netpbm image is too large", read_netpbm(b"P1 4294967296 4294967297 1", 1).unwrap_err().to_string());
+        assert!(read_netpbm(b"P4 18446744073709551615 3 ", 1).is_err());
+    }
+
+    #[test]
+    fn read_sample_above_maximum() {
+        assert_eq!("Netpbm sample 999 exceeds maximum value 10", read_netpbm(b"P2 1 1 10 999", 5).unwrap_err().to_string());
+        assert!(read_netpbm(b"P5 1 1 10\n\x0b", 5).is_err());
+        assert_eq!((1, ".".to_string()), read_netpbm(b"P5 1 1 10\n\x0a", 5).unwrap());
+    }
+
+    #[test]
+    fn render_text_round_trip() {
+        assert_eq!(RENDERED, render_text('#', "HE").unwrap());
+        assert_eq!("RUAKHBEK", ocr('#', &render_text('#', "RUAKHBEK").unwrap()).unwrap());
+        assert!(render_text('#', "HD").is_err());
+    }
+}